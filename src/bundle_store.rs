@@ -0,0 +1,93 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tokio::sync::RwLock as AsyncRwLock;
+
+use crate::response::{content_hash, content_type_for};
+
+/// An emitted asset kept around to be served over HTTP.
+#[derive(Debug, Clone)]
+pub struct StoredAsset {
+    pub content: Bytes,
+    pub content_type: &'static str,
+    /// Strong validator derived from the content, already quoted.
+    pub etag: String,
+}
+
+/// Keeps the output of the most recent compilations addressable as `/bundle/<id>/<path>`.
+///
+/// The store is bounded: once `capacity` bundles are held, the oldest one is dropped.
+pub struct BundleStore {
+    capacity: usize,
+    bundles: AsyncRwLock<Bundles>,
+}
+
+#[derive(Default)]
+struct Bundles {
+    by_id: HashMap<String, Arc<HashMap<String, StoredAsset>>>,
+    order: VecDeque<String>,
+}
+
+impl BundleStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            bundles: AsyncRwLock::new(Bundles::default()),
+        }
+    }
+
+    pub async fn insert(&self, id: &str, files: &HashMap<String, Vec<u8>>) {
+        let assets = files
+            .iter()
+            .map(|(path, content)| {
+                let asset = StoredAsset {
+                    content_type: content_type_for(path),
+                    etag: format!("\"{}\"", content_hash(content)),
+                    content: Bytes::from(content.clone()),
+                };
+                (path.clone(), asset)
+            })
+            .collect();
+
+        let mut bundles = self.bundles.write().await;
+        if bundles.by_id.insert(id.to_string(), Arc::new(assets)).is_none() {
+            bundles.order.push_back(id.to_string());
+        }
+        while bundles.order.len() > self.capacity {
+            if let Some(evicted) = bundles.order.pop_front() {
+                bundles.by_id.remove(&evicted);
+            }
+        }
+    }
+
+    pub async fn contains(&self, id: &str) -> bool {
+        self.bundles.read().await.by_id.contains_key(id)
+    }
+
+    pub async fn get(&self, id: &str, path: &str) -> Option<StoredAsset> {
+        let bundles = self.bundles.read().await;
+        bundles.by_id.get(id)?.get(path).cloned()
+    }
+
+    /// Looks a hot update up in every stored bundle, newest first.
+    ///
+    /// A hot client keeps fetching relative to the bundle it was loaded from, while its updates
+    /// are emitted by later rebuilds stored under other ids.
+    pub async fn get_hot_update(&self, path: &str) -> Option<StoredAsset> {
+        if !is_hot_update(path) {
+            return None;
+        }
+        let bundles = self.bundles.read().await;
+        bundles
+            .order
+            .iter()
+            .rev()
+            .find_map(|id| bundles.by_id.get(id)?.get(path).cloned())
+    }
+}
+
+/// Whether `path` was emitted from `hot_update_main_filename` or `hot_update_chunk_filename`.
+pub fn is_hot_update(path: &str) -> bool {
+    path.contains(".hot-update.")
+}
@@ -0,0 +1,145 @@
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use lru::LruCache;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::config::EdgeCompileConfig;
+use crate::edge_compile::{CompileInput, CompileOutput};
+
+/// Identifies a compilation by its input and effective config.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    pub fn new(input: &CompileInput, config: &EdgeCompileConfig) -> Self {
+        let mut hasher = Sha256::new();
+        match input {
+            CompileInput::Entry(entry) => {
+                hasher.update(b"entry\0");
+                hasher.update(entry.as_deref().unwrap_or_default());
+            }
+            CompileInput::Virtual(sources) => {
+                // Submitted sources never change, so they are part of the key itself
+                hasher.update(b"virtual\0");
+                hasher.update(&sources.entry);
+                for (path, content) in &sources.files {
+                    hasher.update(b"\0");
+                    hasher.update(path);
+                    hasher.update(b"\0");
+                    hasher.update(Sha256::digest(content));
+                }
+            }
+        }
+        hasher.update(b"\0config\0");
+        hasher.update(serde_json::to_vec(config).expect("config is always serializable"));
+        CacheKey(format!("{:x}", hasher.finalize()))
+    }
+}
+
+/// Fingerprints of everything a compilation depended on: size and mtime of each file, and the
+/// lockfile entry of each remote module.
+#[derive(Debug, PartialEq, Eq)]
+struct DependencySnapshot {
+    files: Vec<(PathBuf, Option<(u64, SystemTime)>)>,
+    remote: Vec<(String, Option<String>)>,
+}
+
+impl DependencySnapshot {
+    /// Fingerprints the dependencies of a build that started at `started`. The files are only
+    /// known once it finished, so any modified since it started count as unknown: the build
+    /// may have read them before the change, and the entry must not be fresh with them.
+    fn capture(output: &CompileOutput, lockfile: &Path, started: SystemTime) -> Self {
+        let mut snapshot =
+            Self::from_parts(output.file_dependencies.iter().cloned(), output.remote_modules.iter().cloned(), lockfile);
+        for (_, fingerprint) in &mut snapshot.files {
+            if fingerprint.is_some_and(|(_, modified)| modified >= started) {
+                *fingerprint = None;
+            }
+        }
+        snapshot
+    }
+
+    fn from_parts(
+        files: impl Iterator<Item = PathBuf>,
+        remote: impl Iterator<Item = String>,
+        lockfile: &Path,
+    ) -> Self {
+        Self {
+            files: files
+                .map(|path| {
+                    let fingerprint = std::fs::metadata(&path)
+                        .ok()
+                        .and_then(|metadata| Some((metadata.len(), metadata.modified().ok()?)));
+                    (path, fingerprint)
+                })
+                .collect(),
+            remote: locked_entries(remote, lockfile),
+        }
+    }
+
+    fn is_fresh(&self, lockfile: &Path) -> bool {
+        let current = Self::from_parts(
+            self.files.iter().map(|(path, _)| path.clone()),
+            self.remote.iter().map(|(url, _)| url.clone()),
+            lockfile,
+        );
+        current == *self
+    }
+}
+
+/// The lockfile entry of each of the `urls`, serialized for comparison, or `None` when not locked.
+pub(crate) fn locked_entries(urls: impl Iterator<Item = String>, lockfile: &Path) -> Vec<(String, Option<String>)> {
+    let locked: Option<Value> = std::fs::read(lockfile)
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok());
+    urls.map(|url| {
+        let entry = locked.as_ref().and_then(|locked| locked.get(&url)).map(Value::to_string);
+        (url, entry)
+    })
+    .collect()
+}
+
+struct CacheEntry {
+    output: Arc<CompileOutput>,
+    dependencies: DependencySnapshot,
+}
+
+/// In-process LRU cache of compilation outputs.
+///
+/// A hit is only served after checking that none of the entry's dependencies changed;
+/// stale entries are evicted on lookup.
+pub struct CompileCache {
+    lockfile: PathBuf,
+    entries: Mutex<LruCache<CacheKey, Arc<CacheEntry>>>,
+}
+
+impl CompileCache {
+    pub fn new(capacity: NonZeroUsize, lockfile: PathBuf) -> Self {
+        Self {
+            lockfile,
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<Arc<CompileOutput>> {
+        let entry = self.entries.lock().unwrap().get(key).cloned()?;
+        // Checking the dependencies touches the disk, so it happens outside the lock
+        if entry.dependencies.is_fresh(&self.lockfile) {
+            Some(entry.output.clone())
+        } else {
+            self.entries.lock().unwrap().pop(key);
+            None
+        }
+    }
+
+    /// Caches `output` of a build that started at `started`.
+    pub fn insert(&self, key: CacheKey, output: Arc<CompileOutput>, started: SystemTime) {
+        let dependencies = DependencySnapshot::capture(&output, &self.lockfile, started);
+        let entry = Arc::new(CacheEntry { output, dependencies });
+        self.entries.lock().unwrap().put(key, entry);
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt;
+
+use hyper::StatusCode;
+use rspack_error::Diagnostic;
+use serde::Serialize;
+
+use crate::http_io::FetchError;
+use crate::remote_policy::DENIED_MESSAGE;
+
+/// A serializable view of an rspack diagnostic.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompileDiagnostic {
+    pub title: String,
+    pub message: String,
+    pub module_identifier: Option<String>,
+    pub loc: Option<String>,
+    pub file: Option<String>,
+}
+
+impl CompileDiagnostic {
+    pub fn from_rspack(diagnostic: &Diagnostic) -> Self {
+        Self {
+            title: diagnostic.title(),
+            message: diagnostic.message(),
+            module_identifier: diagnostic.module_identifier().map(|id| id.to_string()),
+            loc: diagnostic.loc(),
+            file: diagnostic.file().map(|file| file.to_string_lossy().to_string()),
+        }
+    }
+
+    /// Classifies by rspack's title where it has one. Remote modules failing without a more
+    /// specific title failed to load: in a locked build that is the lockfile refusing them,
+    /// otherwise the plugin fetching them.
+    fn category(&self, locked: bool) -> Category {
+        match self.title.as_str() {
+            "ModuleNotFoundError" => return Category::Resolve,
+            "ModuleParseError" => return Category::ModuleParse,
+            _ => {}
+        }
+        let message = self.message.to_lowercase();
+        if self.message.contains(DENIED_MESSAGE) {
+            Category::RemoteDenied
+        } else if self.is_remote() {
+            if locked {
+                Category::LockfileMismatch
+            } else {
+                Category::HttpFetch
+            }
+        } else if message.contains("parse failed") || message.contains("parse error") {
+            Category::ModuleParse
+        } else if message.contains("can't resolve") || message.contains("module not found") {
+            Category::Resolve
+        } else {
+            Category::Other
+        }
+    }
+
+    /// Whether the diagnostic comes from a module loaded over HTTP.
+    fn is_remote(&self) -> bool {
+        [&self.module_identifier, &self.file]
+            .into_iter()
+            .flatten()
+            .any(|source| source.contains("http://") || source.contains("https://"))
+    }
+}
+
+impl fmt::Display for CompileDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(module) = &self.module_identifier {
+            write!(f, "[{}] ", module)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(loc) = &self.loc {
+            write!(f, " ({})", loc)?;
+        }
+        Ok(())
+    }
+}
+
+enum Category {
+    RemoteDenied,
+    Resolve,
+    HttpFetch,
+    LockfileMismatch,
+    ModuleParse,
+    Other,
+}
+
+#[derive(Debug)]
+pub enum CompileError {
+    /// The `entry` request is malformed or points at something that does not exist.
+    BadEntry { entry: String, reason: String },
+    /// The compiler config (or a per-request override of it) is invalid.
+    BadConfig(String),
+    /// The request body could not be understood, e.g. a malformed file tree.
+    BadRequest(String),
+    PayloadTooLarge { limit: usize },
+    /// The entry or an import points at a URL the remote policy does not allow.
+    RemoteDenied(Vec<CompileDiagnostic>),
+    Resolve(Vec<CompileDiagnostic>),
+    /// A remote module could not be fetched; `failures` says why, when the HTTP client knows.
+    HttpFetch {
+        diagnostics: Vec<CompileDiagnostic>,
+        failures: Vec<FetchError>,
+    },
+    LockfileMismatch(Vec<CompileDiagnostic>),
+    ModuleParse(Vec<CompileDiagnostic>),
+    /// Compilation errors that do not fall into any of the categories above.
+    Build(Vec<CompileDiagnostic>),
+    /// Failures of the service itself rather than of the code being compiled.
+    Internal(String),
+}
+
+impl CompileError {
+    /// Classifies a failed compilation by its first error; every diagnostic is kept. `locked`
+    /// builds may not change the lockfile, i.e. are not `online`.
+    pub fn from_diagnostics(diagnostics: Vec<CompileDiagnostic>, locked: bool) -> Self {
+        let category = diagnostics
+            .first()
+            .map_or(Category::Other, |diagnostic| diagnostic.category(locked));
+        match category {
+            Category::RemoteDenied => CompileError::RemoteDenied(diagnostics),
+            Category::Resolve => CompileError::Resolve(diagnostics),
+            Category::HttpFetch => CompileError::HttpFetch {
+                diagnostics,
+                failures: Vec::new(),
+            },
+            Category::LockfileMismatch => CompileError::LockfileMismatch(diagnostics),
+            Category::ModuleParse => CompileError::ModuleParse(diagnostics),
+            Category::Other => CompileError::Build(diagnostics),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CompileError::BadEntry { .. } => "badEntry",
+            CompileError::BadConfig(_) => "badConfig",
+            CompileError::BadRequest(_) => "badRequest",
+            CompileError::PayloadTooLarge { .. } => "payloadTooLarge",
+            CompileError::RemoteDenied(_) => "remoteDenied",
+            CompileError::Resolve(_) => "resolve",
+            CompileError::HttpFetch { failures, .. } if failures.iter().any(FetchError::is_integrity) => "integrityMismatch",
+            CompileError::HttpFetch { .. } => "httpFetch",
+            CompileError::LockfileMismatch(_) => "lockfileMismatch",
+            CompileError::ModuleParse(_) => "moduleParse",
+            CompileError::Build(_) => "build",
+            CompileError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            CompileError::BadEntry { .. } | CompileError::BadConfig(_) | CompileError::BadRequest(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            CompileError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            CompileError::RemoteDenied(_) => StatusCode::FORBIDDEN,
+            CompileError::Resolve(_) | CompileError::ModuleParse(_) | CompileError::Build(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            // Content that changed under a pinned hash is a conflict, not a network problem
+            CompileError::HttpFetch { failures, .. } if failures.iter().any(FetchError::is_integrity) => StatusCode::CONFLICT,
+            CompileError::HttpFetch { failures, .. } if !failures.is_empty() && failures.iter().all(FetchError::is_timeout) => {
+                StatusCode::GATEWAY_TIMEOUT
+            }
+            CompileError::HttpFetch { .. } => StatusCode::BAD_GATEWAY,
+            CompileError::LockfileMismatch(_) => StatusCode::CONFLICT,
+            CompileError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Attaches the fetches that failed during the compilation. Whatever rspack made of them,
+    /// the compilation then failed because of the network.
+    pub fn with_fetch_failures(self, failures: Vec<FetchError>) -> Self {
+        if failures.is_empty() || matches!(self, CompileError::RemoteDenied(_)) {
+            return self;
+        }
+        CompileError::HttpFetch {
+            diagnostics: self.diagnostics().to_vec(),
+            failures,
+        }
+    }
+
+    pub fn fetch_failures(&self) -> &[FetchError] {
+        match self {
+            CompileError::HttpFetch { failures, .. } => failures,
+            _ => &[],
+        }
+    }
+
+    pub fn diagnostics(&self) -> &[CompileDiagnostic] {
+        match self {
+            CompileError::RemoteDenied(diagnostics)
+            | CompileError::Resolve(diagnostics)
+            | CompileError::HttpFetch { diagnostics, .. }
+            | CompileError::LockfileMismatch(diagnostics)
+            | CompileError::ModuleParse(diagnostics)
+            | CompileError::Build(diagnostics) => diagnostics,
+            CompileError::BadEntry { .. }
+            | CompileError::BadConfig(_)
+            | CompileError::BadRequest(_)
+            | CompileError::PayloadTooLarge { .. }
+            | CompileError::Internal(_) => &[],
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::BadEntry { entry, reason } => write!(f, "bad entry `{}`: {}", entry, reason),
+            CompileError::BadConfig(message) => write!(f, "bad config: {}", message),
+            CompileError::BadRequest(message) => write!(f, "bad request: {}", message),
+            CompileError::PayloadTooLarge { limit } => write!(f, "request body exceeds {} bytes", limit),
+            CompileError::RemoteDenied(_) => write!(f, "remote URL is not allowed"),
+            CompileError::Resolve(_) => write!(f, "failed to resolve a module"),
+            CompileError::HttpFetch { failures, .. } if failures.iter().any(FetchError::is_integrity) => {
+                write!(f, "remote module does not match its integrity")
+            }
+            CompileError::HttpFetch { .. } => write!(f, "failed to fetch a remote module"),
+            CompileError::LockfileMismatch(_) => write!(f, "remote module does not match the lockfile"),
+            CompileError::ModuleParse(_) => write!(f, "failed to parse a module"),
+            CompileError::Build(_) => write!(f, "compilation failed"),
+            CompileError::Internal(message) => write!(f, "internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
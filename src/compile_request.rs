@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
+use bytes::Bytes;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::compile_error::CompileError;
+use crate::edge_compile::VirtualSources;
+
+/// Upper bound for `POST /compile` bodies.
+pub const MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// A `POST /compile` body: a virtual file tree, the entry inside it, and an optional partial config.
+pub struct CompileRequestBody {
+    pub sources: VirtualSources,
+    pub config: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonCompileRequest {
+    /// May be left out when the config names `entries`.
+    #[serde(default)]
+    entry: String,
+    files: BTreeMap<String, String>,
+    #[serde(default)]
+    config: Option<Value>,
+}
+
+/// Parses either a JSON body (`{ "entry", "files": { path: source }, "config" }`) or a
+/// `multipart/form-data` body where the `entry` and `config` fields are text and every other
+/// field is a file whose path is the field name.
+pub async fn parse(content_type: Option<&str>, body: Bytes) -> Result<CompileRequestBody, CompileError> {
+    let content_type = content_type.unwrap_or("application/json");
+    if content_type.starts_with("multipart/form-data") {
+        parse_multipart(content_type, body).await
+    } else if content_type.starts_with("application/json") {
+        let request: JsonCompileRequest = serde_json::from_slice(&body)
+            .map_err(|err| CompileError::BadRequest(format!("invalid JSON body: {}", err)))?;
+        Ok(CompileRequestBody {
+            sources: VirtualSources {
+                entry: request.entry,
+                files: request
+                    .files
+                    .into_iter()
+                    .map(|(path, source)| (path, source.into_bytes()))
+                    .collect(),
+            },
+            config: request.config,
+        })
+    } else {
+        Err(CompileError::BadRequest(format!("unsupported content type `{}`", content_type)))
+    }
+}
+
+async fn parse_multipart(content_type: &str, body: Bytes) -> Result<CompileRequestBody, CompileError> {
+    let bad_request = |err: multer::Error| CompileError::BadRequest(format!("invalid multipart body: {}", err));
+    let boundary = multer::parse_boundary(content_type).map_err(bad_request)?;
+    let stream = futures::stream::once(async move { Ok::<Bytes, Infallible>(body) });
+    let mut multipart = multer::Multipart::new(stream, boundary);
+
+    let mut entry = None;
+    let mut config = None;
+    let mut files = BTreeMap::new();
+    while let Some(field) = multipart.next_field().await.map_err(bad_request)? {
+        let Some(name) = field.name().map(str::to_string) else {
+            return Err(CompileError::BadRequest("multipart field without a name".to_string()));
+        };
+        let data = field.bytes().await.map_err(bad_request)?;
+        match name.as_str() {
+            "entry" => entry = Some(String::from_utf8_lossy(&data).to_string()),
+            "config" => {
+                config = Some(serde_json::from_slice(&data).map_err(|err| {
+                    CompileError::BadConfig(format!("`config` field is not valid JSON: {}", err))
+                })?)
+            }
+            _ => {
+                files.insert(name, data.to_vec());
+            }
+        }
+    }
+
+    Ok(CompileRequestBody {
+        sources: VirtualSources {
+            entry: entry.unwrap_or_default(),
+            files,
+        },
+        config,
+    })
+}
@@ -0,0 +1,302 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rspack_core::Plugin;
+use rspack_fs::{ReadableFileSystem, WritableFileSystem};
+use rspack_paths::Utf8PathBuf;
+use rspack_plugin_schemes::HttpClient;
+
+use crate::compile_error::CompileError;
+use crate::config::EdgeCompileConfig;
+use crate::edge_compile::{self, CompileEnv, CompileInput, CompileOutput, DefaultPlugin, PluginFactory, PreparedCompilation};
+use crate::remote_policy::RemotePolicy;
+
+/// Compiles entries the way the server does, for use from other Rust code.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use rspack_rust_api::{CompileError, CompileInput, EdgeCompileConfig, EdgeCompiler, MockFileSystem};
+/// # async fn example(config: EdgeCompileConfig, sources: MockFileSystem) -> Result<(), CompileError> {
+/// let compiler = EdgeCompiler::builder()
+///     .config(config)
+///     .input_filesystem(Arc::new(sources))
+///     .build()?;
+/// let output = compiler.compile(CompileInput::Entry(Some("/src/index.js".into()))).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// An `EdgeCompiler` is cheap to share; every [`EdgeCompiler::compile`] creates a fresh rspack
+/// compiler, so concurrent compilations do not interfere.
+#[derive(Clone)]
+pub struct EdgeCompiler {
+    config: EdgeCompileConfig,
+    env: CompileEnv,
+}
+
+impl EdgeCompiler {
+    pub fn builder() -> EdgeCompilerBuilder {
+        EdgeCompilerBuilder::default()
+    }
+
+    pub fn config(&self) -> &EdgeCompileConfig {
+        &self.config
+    }
+
+    /// Compiles `input` once from scratch.
+    pub async fn compile(&self, input: CompileInput) -> Result<CompileOutput, CompileError> {
+        self.prepare(input).await?.build().await
+    }
+
+    /// Creates the compiler for `input` without running it. Keep the result around to
+    /// [`PreparedCompilation::rebuild`] it incrementally when files change.
+    pub async fn prepare(&self, input: CompileInput) -> Result<PreparedCompilation, CompileError> {
+        edge_compile::prepare_with(input, &self.config, &self.env).await
+    }
+}
+
+/// Sets up an [`EdgeCompiler`]. Anything not set behaves as in the server: the default
+/// config, the current directory, the disk as input and the configured HTTP clients.
+#[derive(Default)]
+pub struct EdgeCompilerBuilder {
+    config: EdgeCompileConfig,
+    env: CompileEnv,
+}
+
+impl EdgeCompilerBuilder {
+    pub fn config(mut self, config: EdgeCompileConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Directory local entries, the HTTP cache, the lockfile and recordings resolve against.
+    pub fn context(mut self, context: impl Into<PathBuf>) -> Self {
+        self.env.context = Some(context.into());
+        self
+    }
+
+    /// Reads entry sources from `filesystem` instead of the disk.
+    pub fn input_filesystem(mut self, filesystem: Arc<dyn ReadableFileSystem + Send + Sync>) -> Self {
+        self.env.input_filesystem = Some(filesystem);
+        self
+    }
+
+    /// Also writes every emitted asset to `filesystem` below `directory`.
+    pub fn output_filesystem(
+        mut self,
+        filesystem: Arc<dyn WritableFileSystem + Send + Sync>,
+        directory: impl Into<Utf8PathBuf>,
+    ) -> Self {
+        self.env.output_filesystem = Some((filesystem, directory.into()));
+        self
+    }
+
+    /// Fetches remote modules through `client`. The remote policy and integrity checks of the
+    /// config still apply on top of it.
+    pub fn http_client(mut self, client: Arc<dyn HttpClient>) -> Self {
+        self.env.http_client = Some(client);
+        self
+    }
+
+    /// Adds a plugin to every compilation, after the built-in ones.
+    pub fn plugin<P, F>(mut self, factory: F) -> Self
+    where
+        P: Plugin + 'static,
+        F: Fn() -> P + Send + Sync + 'static,
+    {
+        self.env.plugins.push(plugin_factory(factory));
+        self
+    }
+
+    /// Uses the plugin `factory` creates instead of the built-in `plugin`, e.g. for ids none of
+    /// the `plugins` presets offer. Takes precedence over the presets.
+    pub fn replace_plugin<P, F>(mut self, plugin: DefaultPlugin, factory: F) -> Self
+    where
+        P: Plugin + 'static,
+        F: Fn() -> P + Send + Sync + 'static,
+    {
+        self.env.replaced_plugins.insert(plugin, Some(plugin_factory(factory)));
+        self
+    }
+
+    /// Leaves the built-in `plugin` out of every compilation.
+    pub fn remove_plugin(mut self, plugin: DefaultPlugin) -> Self {
+        self.env.replaced_plugins.insert(plugin, None);
+        self
+    }
+
+    /// Checks the config, so mistakes surface here rather than on the first compilation.
+    pub fn build(self) -> Result<EdgeCompiler, CompileError> {
+        if self.config.hot && matches!(self.env.replaced_plugins.get(&DefaultPlugin::DataUri), Some(None)) {
+            return Err(CompileError::BadConfig("`hot` needs the DataUri plugin for its client".to_string()));
+        }
+        self.config.to_compiler_options("/", Utf8PathBuf::from(edge_compile::OUTPUT_ROOT))?;
+        RemotePolicy::new(&self.config.remote)?;
+        Ok(EdgeCompiler {
+            config: self.config,
+            env: self.env,
+        })
+    }
+}
+
+fn plugin_factory<P, F>(factory: F) -> PluginFactory
+where
+    P: Plugin + 'static,
+    F: Fn() -> P + Send + Sync + 'static,
+{
+    Arc::new(move || Box::new(factory()) as Box<dyn Plugin>)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+    use rspack_plugin_schemes::{DataUriPlugin, HttpResponse};
+
+    use super::*;
+    use crate::memory_fs::MockFileSystem;
+
+    const DATA_ENTRY: &str = "data:text/javascript,console.log('from a data URI')";
+
+    /// Serves one module for every URL.
+    #[derive(Debug)]
+    struct StubHttpClient;
+
+    #[async_trait]
+    impl HttpClient for StubHttpClient {
+        async fn get(&self, _url: &str, _headers: &HashMap<String, String>) -> anyhow::Result<HttpResponse> {
+            Ok(HttpResponse {
+                status: 200,
+                headers: HashMap::from([("content-type".to_string(), "application/javascript".to_string())]),
+                body: b"export const answer = 'answer from the stub';".to_vec(),
+            })
+        }
+    }
+
+    // A working directory with its own `package.json`, so the HTTP cache and lockfile live
+    // inside it. The sources only exist in memory below it.
+    fn scratch_context(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rspack-rust-api-compiler-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("package.json"), "{}").unwrap();
+        dir
+    }
+
+    fn sources(context: &Path, answer: &str) -> MockFileSystem {
+        let sources = MockFileSystem::new();
+        sources.insert(
+            &context.join("src/index.js"),
+            format!("import {{ answer }} from '{}';\nconsole.log(answer);\n", answer).into_bytes(),
+        );
+        sources.insert(&context.join("src/answer.js"), b"export const answer = 'answer from memory';".to_vec());
+        sources
+    }
+
+    fn entry() -> CompileInput {
+        CompileInput::Entry(Some("./src/index.js".to_string()))
+    }
+
+    fn main_js(output: &CompileOutput) -> String {
+        String::from_utf8(output.files["main.js"].clone()).unwrap()
+    }
+
+    // Counts how often the plugin is created, i.e. how many compilations use it.
+    fn counted_data_uri(created: &Arc<AtomicUsize>) -> impl Fn() -> DataUriPlugin + Send + Sync + 'static {
+        let created = created.clone();
+        move || {
+            created.fetch_add(1, Ordering::SeqCst);
+            DataUriPlugin::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn compiles_from_the_input_filesystem_into_the_output_filesystem() {
+        let context = scratch_context("filesystems");
+        let emitted = MockFileSystem::new();
+        let compiler = EdgeCompiler::builder()
+            .context(&context)
+            .input_filesystem(Arc::new(sources(&context, "./answer.js")))
+            .output_filesystem(Arc::new(emitted.clone()), "/dist")
+            .build()
+            .unwrap();
+
+        let output = compiler.compile(entry()).await.unwrap();
+        assert!(main_js(&output).contains("answer from memory"));
+        assert_eq!(emitted.read(Path::new("/dist/main.js")).unwrap(), output.files["main.js"]);
+        std::fs::remove_dir_all(context).unwrap();
+    }
+
+    #[tokio::test]
+    async fn fetches_remote_modules_through_the_http_client() {
+        let context = scratch_context("http-client");
+        let compiler = EdgeCompiler::builder()
+            .context(&context)
+            .input_filesystem(Arc::new(sources(&context, "https://example.com/answer.js")))
+            .http_client(Arc::new(StubHttpClient))
+            .build()
+            .unwrap();
+
+        let output = compiler.compile(entry()).await.unwrap();
+        assert_eq!(output.remote_modules, vec!["https://example.com/answer.js".to_string()]);
+        assert!(main_js(&output).contains("answer from the stub"));
+        std::fs::remove_dir_all(context).unwrap();
+    }
+
+    #[tokio::test]
+    async fn removed_plugins_are_left_out() {
+        let context = scratch_context("remove-plugin");
+        let compiler = EdgeCompiler::builder().context(&context).build().unwrap();
+        compiler.compile(CompileInput::Entry(Some(DATA_ENTRY.to_string()))).await.unwrap();
+
+        let compiler = EdgeCompiler::builder()
+            .context(&context)
+            .remove_plugin(DefaultPlugin::DataUri)
+            .build()
+            .unwrap();
+        assert!(compiler.compile(CompileInput::Entry(Some(DATA_ENTRY.to_string()))).await.is_err());
+        std::fs::remove_dir_all(context).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replaced_and_added_plugins_are_created_per_compilation() {
+        let context = scratch_context("replace-plugin");
+        let replaced = Arc::new(AtomicUsize::new(0));
+        let compiler = EdgeCompiler::builder()
+            .context(&context)
+            .replace_plugin(DefaultPlugin::DataUri, counted_data_uri(&replaced))
+            .build()
+            .unwrap();
+        compiler.compile(CompileInput::Entry(Some(DATA_ENTRY.to_string()))).await.unwrap();
+        compiler.compile(CompileInput::Entry(Some(DATA_ENTRY.to_string()))).await.unwrap();
+        assert_eq!(replaced.load(Ordering::SeqCst), 2);
+
+        // Removing the built-in one and adding it back as an extra plugin works the same
+        let added = Arc::new(AtomicUsize::new(0));
+        let compiler = EdgeCompiler::builder()
+            .context(&context)
+            .remove_plugin(DefaultPlugin::DataUri)
+            .plugin(counted_data_uri(&added))
+            .build()
+            .unwrap();
+        compiler.compile(CompileInput::Entry(Some(DATA_ENTRY.to_string()))).await.unwrap();
+        assert_eq!(added.load(Ordering::SeqCst), 1);
+        std::fs::remove_dir_all(context).unwrap();
+    }
+
+    #[test]
+    fn hot_compilations_need_the_data_uri_plugin() {
+        let config = EdgeCompileConfig {
+            hot: true,
+            ..EdgeCompileConfig::default()
+        };
+        let result = EdgeCompiler::builder()
+            .config(config)
+            .remove_plugin(DefaultPlugin::DataUri)
+            .build();
+        assert!(matches!(result, Err(CompileError::BadConfig(_))));
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use lru::LruCache;
+use serde::Serialize;
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::compile_cache::{locked_entries, CacheKey};
+use crate::compile_error::CompileError;
+use crate::config::EdgeCompileConfig;
+use crate::edge_compile::{self, CompileInput, CompileOutput, PreparedCompilation};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RebuildKind {
+    /// First build of a freshly created compiler.
+    Cold,
+    /// Rebuild of a warm compiler with the files that changed since its last build.
+    Incremental,
+    /// Nothing changed since the last build, its output was reused.
+    Unchanged,
+}
+
+/// How a pooled compilation was produced, reported alongside its output.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebuildInfo {
+    pub kind: RebuildKind,
+    pub duration_ms: f64,
+    pub changed_files: Vec<String>,
+    pub removed_files: Vec<String>,
+}
+
+struct WarmCompiler {
+    compilation: PreparedCompilation,
+    /// Size and mtime of every file dependency as of the last build.
+    snapshot: HashMap<PathBuf, Fingerprint>,
+    /// Lockfile entry of every remote module of the last build.
+    locked: Vec<(String, Option<String>)>,
+    last_output: Option<Arc<CompileOutput>>,
+}
+
+type Fingerprint = Option<(u64, SystemTime)>;
+
+impl WarmCompiler {
+    /// Fingerprints the dependencies of the last build as they are now, before the next one.
+    fn fingerprints(&self) -> HashMap<PathBuf, Fingerprint> {
+        self.snapshot.keys().map(|path| (path.clone(), fingerprint(path))).collect()
+    }
+
+    /// Records the dependencies of the build that just finished. Those already known were
+    /// fingerprinted in `before`, ahead of the build, so a change made while it ran shows up
+    /// in the next one. New ones modified since the build `started` are recorded as unknown
+    /// for the same reason.
+    fn take_snapshot(&mut self, mut before: HashMap<PathBuf, Fingerprint>, started: SystemTime) {
+        self.snapshot = self
+            .compilation
+            .file_dependencies()
+            .into_iter()
+            .map(|path| {
+                let fingerprint = before
+                    .remove(&path)
+                    .unwrap_or_else(|| fingerprint(&path).filter(|(_, modified)| *modified < started));
+                (path, fingerprint)
+            })
+            .collect();
+    }
+
+    fn changes(&self, current: &HashMap<PathBuf, Fingerprint>) -> (HashSet<String>, HashSet<String>) {
+        let mut changed = HashSet::new();
+        let mut removed = HashSet::new();
+        for (path, previous) in &self.snapshot {
+            let current = current.get(path).copied().flatten();
+            if current == *previous {
+                continue;
+            }
+            let path = path.to_string_lossy().to_string();
+            if current.is_none() {
+                removed.insert(path);
+            } else {
+                changed.insert(path);
+            }
+        }
+        (changed, removed)
+    }
+
+    /// Whether the lockfile entry of a remote module changed since the last build, after an
+    /// upgrade, a prune or an online build of another compiler. Rebuilds only pick up local
+    /// changes, so those call for a fresh compiler.
+    fn relocked(&self, lockfile: &Path) -> bool {
+        let urls = self.locked.iter().map(|(url, _)| url.clone());
+        locked_entries(urls, lockfile) != self.locked
+    }
+}
+
+fn fingerprint(path: &Path) -> Fingerprint {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+/// Long-lived compilers for entry requests, one per entry and config.
+///
+/// Each compiler keeps its module graph between requests and is rebuilt incrementally with
+/// the files that changed since its last build. Requests for the same compiler are serialized.
+pub struct CompilerPool {
+    lockfile: PathBuf,
+    compilers: Mutex<LruCache<CacheKey, Arc<AsyncMutex<Option<WarmCompiler>>>>>,
+    /// Compilers dropped to make room for others, until taken with [`CompilerPool::take_evicted`].
+    evicted: Mutex<Vec<CacheKey>>,
+}
+
+impl CompilerPool {
+    pub fn new(capacity: NonZeroUsize, lockfile: PathBuf) -> Self {
+        Self {
+            lockfile,
+            compilers: Mutex::new(LruCache::new(capacity)),
+            evicted: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns and forgets the keys of the compilers evicted so far.
+    pub fn take_evicted(&self) -> Vec<CacheKey> {
+        std::mem::take(&mut *self.evicted.lock().unwrap())
+    }
+
+    pub async fn compile(
+        &self,
+        key: CacheKey,
+        input: CompileInput,
+        config: &EdgeCompileConfig,
+    ) -> Result<(Arc<CompileOutput>, RebuildInfo), CompileError> {
+        let slot = {
+            let mut compilers = self.compilers.lock().unwrap();
+            match compilers.get(&key) {
+                Some(slot) => slot.clone(),
+                None => {
+                    let slot = Arc::new(AsyncMutex::new(None));
+                    if let Some((evicted, _)) = compilers.push(key, slot.clone()) {
+                        self.evicted.lock().unwrap().push(evicted);
+                    }
+                    slot
+                }
+            }
+        };
+        let mut slot = slot.lock().await;
+        let start_time = Instant::now();
+
+        if slot.as_ref().is_some_and(|warm| warm.relocked(&self.lockfile)) {
+            *slot = None;
+        }
+        if slot.is_none() {
+            let config = EdgeCompileConfig {
+                incremental: true,
+                ..config.clone()
+            };
+            let compilation = edge_compile::prepare(input, &config).await?;
+            *slot = Some(WarmCompiler {
+                compilation,
+                snapshot: HashMap::new(),
+                locked: Vec::new(),
+                last_output: None,
+            });
+        }
+        let warm = slot.as_mut().expect("compiler was created above");
+
+        let build_started = SystemTime::now();
+        let before = warm.fingerprints();
+        let (changed, removed) = warm.changes(&before);
+        let (kind, result) = match &warm.last_output {
+            Some(output) if changed.is_empty() && removed.is_empty() => {
+                (RebuildKind::Unchanged, Ok(output.clone()))
+            }
+            Some(_) => (
+                RebuildKind::Incremental,
+                warm.compilation.rebuild(changed.clone(), removed.clone()).await.map(Arc::new),
+            ),
+            // Never built, or the last build failed and left no graph worth rebuilding from
+            None => (RebuildKind::Cold, warm.compilation.build().await.map(Arc::new)),
+        };
+        warm.take_snapshot(before, build_started);
+        warm.locked = match &result {
+            Ok(output) => locked_entries(output.remote_modules.iter().cloned(), &self.lockfile),
+            Err(_) => Vec::new(),
+        };
+        warm.last_output = result.as_ref().ok().cloned();
+
+        let output = result?;
+        Ok((
+            output,
+            RebuildInfo {
+                kind,
+                duration_ms: duration_ms(start_time.elapsed()),
+                changed_files: sorted(changed),
+                removed_files: sorted(removed),
+            },
+        ))
+    }
+}
+
+fn duration_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn sorted(paths: HashSet<String>) -> Vec<String> {
+    let mut paths: Vec<String> = paths.into_iter().collect();
+    paths.sort();
+    paths
+}
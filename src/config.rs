@@ -0,0 +1,804 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{bail, Context as _};
+use rspack_core::{
+    CacheOptions, ChunkLoading, ChunkLoadingType, CompilerOptions, CrossOriginLoading,
+    DevServerOptions, DynamicImportFetchPriority, DynamicImportMode, EntryOptions, EntryRuntime,
+    Environment, Experiments, Filename, HashDigest, HashFunction, HashSalt, Incremental,
+    JavascriptParserOptions, JavascriptParserOrder, JavascriptParserUrl, LibraryName,
+    LibraryNonUmdObject, LibraryOptions, MangleExportsOption, Mode, ModuleOptions,
+    ModuleType, Optimization, OutputOptions, ParserOptions, ParserOptionsMap, PathInfo,
+    PublicPath, Resolve, RspackFuture, SideEffectOption, SnapshotOptions, StatsOptions, Target,
+    UsedExportsOption, WasmLoading,
+};
+use rspack_paths::Utf8PathBuf;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::compile_error::CompileError;
+
+/// User-facing compiler settings, translated into rspack's `CompilerOptions` per compilation.
+///
+/// Loaded once at startup (TOML or JSON) and optionally patched per request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct EdgeCompileConfig {
+    pub mode: ModeConfig,
+    pub target: Vec<String>,
+    pub resolve: ResolveConfig,
+    pub output: OutputConfig,
+    pub parser: ParserConfig,
+    pub remote: RemoteConfig,
+    pub http: HttpConfig,
+    /// Lets rebuilds reuse the module graph and skip re-emitting unchanged assets.
+    pub incremental: bool,
+    /// Enables Hot Module Replacement and injects the client that applies updates from `/events`.
+    pub hot: bool,
+    /// Which variant the built-in plugins that have alternatives use.
+    pub plugins: PluginPresets,
+    /// Switches the optimizations `mode` picks on or off individually.
+    pub optimization: OptimizationConfig,
+    /// Named entry points compiled together, next to the request's own entry (named `main`)
+    /// when it has one.
+    pub entries: BTreeMap<String, EntryConfig>,
+}
+
+/// Overrides for the optimizations of the mode: `production` enables all of them, the other
+/// modes none. Unset fields follow the mode.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct OptimizationConfig {
+    /// Works out which exports every module provides. The optimizations below need it, so it
+    /// is on whenever one of them is.
+    pub provided_exports: Option<bool>,
+    /// Works out which exports are used, so unused ones are dropped.
+    pub used_exports: Option<bool>,
+    /// Skips modules marked free of side effects when none of their exports are used.
+    pub side_effects: Option<bool>,
+    /// Hoists modules into a single scope where possible.
+    pub concatenate_modules: Option<bool>,
+    /// Shortens export names, deterministically across builds.
+    pub mangle_exports: Option<bool>,
+    /// Minifies JavaScript assets with SWC.
+    pub minimize: Option<bool>,
+}
+
+/// The optimizations a compilation runs with, see [`EdgeCompileConfig::optimization`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Optimizations {
+    pub provided_exports: bool,
+    pub used_exports: bool,
+    pub side_effects: bool,
+    pub concatenate_modules: bool,
+    pub mangle_exports: bool,
+    pub minimize: bool,
+}
+
+/// One named entry point and the options that set it apart from the output defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct EntryConfig {
+    /// The entry module, resolved like the `entry` of the request.
+    pub import: String,
+    /// Name of the chunk the runtime goes into; entries naming the same runtime share it.
+    #[serde(default)]
+    pub runtime: Option<String>,
+    /// Entries whose modules this one uses instead of bundling its own copies.
+    #[serde(default)]
+    pub depend_on: Vec<String>,
+    /// Overrides `output.filename`.
+    #[serde(default)]
+    pub filename: Option<String>,
+    /// Overrides `output.publicPath`.
+    #[serde(default)]
+    pub public_path: Option<String>,
+    /// Exposes the exports of the entry module as a library.
+    #[serde(default)]
+    pub library: Option<LibraryConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct LibraryConfig {
+    /// rspack library type, e.g. `module`, `commonjs2`, `umd`, `system` or `var`.
+    #[serde(rename = "type")]
+    pub library_type: String,
+    /// Global or module name, for the types that have one.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Exposes only this export (or nested export) instead of the whole namespace.
+    #[serde(default)]
+    pub export: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModeConfig {
+    Development,
+    Production,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct ResolveConfig {
+    pub extensions: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct OutputConfig {
+    pub filename: String,
+    pub chunk_filename: String,
+    pub css_filename: String,
+    pub css_chunk_filename: String,
+    pub asset_module_filename: String,
+    pub source_map_filename: String,
+    pub hot_update_main_filename: String,
+    pub hot_update_chunk_filename: String,
+    /// `"auto"` or a literal prefix such as `/static/`.
+    pub public_path: String,
+    pub unique_name: String,
+    pub global_object: String,
+    /// `type` of the `<script>` tags chunks are loaded with; only used by the `script` format,
+    /// the others imply their own.
+    pub script_type: String,
+    pub hash_function: HashFunctionConfig,
+    pub hash_digest_length: usize,
+    pub hash_salt: Option<String>,
+    pub chunk_load_timeout: u32,
+    /// Module format of the bundle, which also decides how its chunks are loaded.
+    pub format: OutputFormat,
+    /// Name the `umd` and `system` formats register the entry's exports under.
+    pub library_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Plain scripts loading their chunks through JSONP, exporting nothing.
+    Script,
+    /// An ES module exporting the entry's exports, chunks loaded with `import()`.
+    Esm,
+    /// A CommonJS module (`module.exports`), chunks loaded with `require`.
+    Commonjs,
+    /// Works as AMD, CommonJS or a global named `libraryName`; chunks loaded through JSONP.
+    Umd,
+    /// A `System.register` module; chunks loaded through JSONP.
+    System,
+}
+
+impl OutputFormat {
+    /// rspack library type the entry is exposed as, if any.
+    pub fn library_type(self) -> Option<&'static str> {
+        match self {
+            OutputFormat::Script => None,
+            OutputFormat::Esm => Some("module"),
+            OutputFormat::Commonjs => Some("commonjs2"),
+            OutputFormat::Umd => Some("umd"),
+            OutputFormat::System => Some("system"),
+        }
+    }
+
+    pub fn chunk_loading(self) -> ChunkLoadingType {
+        match self {
+            OutputFormat::Script | OutputFormat::Umd | OutputFormat::System => ChunkLoadingType::Jsonp,
+            OutputFormat::Esm => ChunkLoadingType::Import,
+            OutputFormat::Commonjs => ChunkLoadingType::Require,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashFunctionConfig {
+    Md4,
+    Xxhash64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct ParserConfig {
+    pub dynamic_import_mode: DynamicImportModeConfig,
+    pub dynamic_import_prefetch: Option<i32>,
+    pub dynamic_import_preload: Option<i32>,
+    pub import_meta: bool,
+    pub url: bool,
+    pub expr_context_critical: bool,
+    pub wrapped_context_critical: bool,
+    pub strict_export_presence: bool,
+}
+
+/// Which remote URLs entries and imports may be fetched from, see `RemotePolicy`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct RemoteConfig {
+    /// URLs that may be fetched; an empty list allows any public URL.
+    pub allow: Vec<UrlPattern>,
+    /// URLs that are never fetched, even when allowed above.
+    pub deny: Vec<UrlPattern>,
+    /// Lets hosts on loopback, private and link-local networks be fetched.
+    pub allow_private_networks: bool,
+    /// Expected SRI hashes (`sha256-`, `sha384-` or `sha512-`) of remote modules by URL.
+    pub integrity: BTreeMap<String, String>,
+}
+
+/// How remote modules are fetched.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct HttpConfig {
+    pub mode: FetchMode,
+    pub client: HttpClientMode,
+    /// Where `replay` reads and `record` writes recorded responses, relative to the working
+    /// directory.
+    pub recordings: String,
+    /// Proxy for every remote fetch, e.g. `http://proxy:3128`. When unset, `HTTPS_PROXY` and
+    /// `HTTP_PROXY` (or their lowercase forms) are used for the matching scheme.
+    pub proxy: Option<String>,
+    /// Comma-separated hosts, domains and CIDR ranges that bypass the proxy. Defaults to `NO_PROXY`.
+    pub no_proxy: Option<String>,
+    /// Basic auth for the proxy. Credentials in the proxy URL itself work too.
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>,
+    pub connect_timeout_ms: u64,
+    /// Limit for a single attempt, from connecting until the whole body is read.
+    pub timeout_ms: u64,
+    /// How often a fetch is retried after a network error, a 5xx or a 429.
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every further one.
+    pub retry_backoff_ms: u64,
+    /// Upper bound for backoff and for `Retry-After`.
+    pub max_retry_delay_ms: u64,
+    /// Largest response body accepted, in bytes.
+    pub max_response_size: usize,
+    /// How many redirects a fetch follows before it fails.
+    pub max_redirects: usize,
+}
+
+impl HttpConfig {
+    /// Lowers every timeout, retry and size limit that exceeds the one of `server`, so a
+    /// request cannot make the server wait longer or fetch more than it is configured to.
+    fn cap_limits(&mut self, server: &HttpConfig) {
+        self.connect_timeout_ms = self.connect_timeout_ms.min(server.connect_timeout_ms);
+        self.timeout_ms = self.timeout_ms.min(server.timeout_ms);
+        self.retries = self.retries.min(server.retries);
+        self.retry_backoff_ms = self.retry_backoff_ms.min(server.retry_backoff_ms);
+        self.max_retry_delay_ms = self.max_retry_delay_ms.min(server.max_retry_delay_ms);
+        self.max_response_size = self.max_response_size.min(server.max_response_size);
+        self.max_redirects = self.max_redirects.min(server.max_redirects);
+    }
+}
+
+/// Whether remote modules may be fetched from the network, ordered from least to most strict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FetchMode {
+    /// Fetch what is missing or stale and record it in the lockfile.
+    Online,
+    /// The lockfile must already cover every remote module; cached misses are errors.
+    Frozen,
+    /// Never touch the network, serve remote modules from the HTTP cache only.
+    Offline,
+}
+
+/// Where responses for remote modules come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpClientMode {
+    /// Fetch over the network, through the HTTP cache.
+    Network,
+    /// Serve recorded responses only, never touching the network.
+    Replay,
+    /// Fetch over the network and record every response for `replay`.
+    Record,
+}
+
+/// Named presets for the built-in plugins, e.g. `plugins = { moduleIds = "deterministic" }`.
+/// Unset presets follow the mode, see [`EdgeCompileConfig::plugin_presets`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct PluginPresets {
+    pub module_ids: Option<IdsPreset>,
+    pub chunk_ids: Option<IdsPreset>,
+}
+
+/// The presets in effect once the mode defaults are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdsPresets {
+    pub module_ids: IdsPreset,
+    pub chunk_ids: IdsPreset,
+}
+
+/// How modules or chunks are identified in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdsPreset {
+    /// Readable ids derived from paths, e.g. `./src/index.js`.
+    Named,
+    /// Numbers in order of use; they shift whenever modules are added or removed.
+    Natural,
+    /// Short hashes that stay the same across builds, for long-term caching.
+    Deterministic,
+}
+
+/// A URL prefix such as `"https://esm.sh/"`, or `{ regex = "..." }` matched against the whole URL.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum UrlPattern {
+    Prefix(String),
+    Regex { regex: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DynamicImportModeConfig {
+    Eager,
+    Lazy,
+    LazyOnce,
+    Weak,
+}
+
+impl Default for EdgeCompileConfig {
+    fn default() -> Self {
+        Self {
+            mode: ModeConfig::Development,
+            target: vec!["es2022".to_string()],
+            resolve: ResolveConfig::default(),
+            output: OutputConfig::default(),
+            parser: ParserConfig::default(),
+            remote: RemoteConfig::default(),
+            http: HttpConfig::default(),
+            incremental: false,
+            hot: false,
+            plugins: PluginPresets::default(),
+            optimization: OptimizationConfig::default(),
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            mode: FetchMode::Online,
+            client: HttpClientMode::Network,
+            recordings: "fixtures/http".to_string(),
+            proxy: None,
+            no_proxy: None,
+            proxy_username: None,
+            proxy_password: None,
+            connect_timeout_ms: 10_000,
+            timeout_ms: 60_000,
+            retries: 2,
+            retry_backoff_ms: 250,
+            max_retry_delay_ms: 10_000,
+            max_response_size: 50 * 1024 * 1024,
+            max_redirects: 10,
+        }
+    }
+}
+
+impl Default for ResolveConfig {
+    fn default() -> Self {
+        Self {
+            extensions: vec![".js".to_string()],
+        }
+    }
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            filename: "[name].js".to_string(),
+            chunk_filename: "[id].js".to_string(),
+            css_filename: "[name].css".to_string(),
+            css_chunk_filename: "[id].css".to_string(),
+            asset_module_filename: "asset-[name].js".to_string(),
+            source_map_filename: "[file].map".to_string(),
+            hot_update_main_filename: "[name].[hash].hot-update.js".to_string(),
+            hot_update_chunk_filename: "[id].[hash].hot-update.js".to_string(),
+            public_path: "auto".to_string(),
+            unique_name: "main".to_string(),
+            global_object: "window".to_string(),
+            script_type: "javascript/module".to_string(),
+            hash_function: HashFunctionConfig::Md4,
+            hash_digest_length: 20,
+            hash_salt: Some("salt".to_string()),
+            chunk_load_timeout: 120000,
+            format: OutputFormat::Script,
+            library_name: None,
+        }
+    }
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            dynamic_import_mode: DynamicImportModeConfig::Eager,
+            dynamic_import_prefetch: Some(1),
+            dynamic_import_preload: Some(0),
+            import_meta: false,
+            url: false,
+            expr_context_critical: false,
+            wrapped_context_critical: false,
+            strict_export_presence: false,
+        }
+    }
+}
+
+impl EdgeCompileConfig {
+    /// Reads a config file, picking the format from its extension (`.toml` or `.json`).
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&source)
+                .with_context(|| format!("invalid TOML config {}", path.display())),
+            Some("json") => serde_json::from_str(&source)
+                .with_context(|| format!("invalid JSON config {}", path.display())),
+            _ => bail!("unsupported config format {}, expected .toml or .json", path.display()),
+        }
+    }
+
+    /// Applies a partial config on top of this one; objects are merged key by key,
+    /// everything else is replaced.
+    pub fn with_overrides(&self, overrides: &Value) -> Result<Self, CompileError> {
+        let mut merged = serde_json::to_value(self).expect("config is always serializable");
+        merge_json(&mut merged, overrides);
+        serde_json::from_value(merged).map_err(|err| CompileError::BadConfig(err.to_string()))
+    }
+
+    /// Applies overrides sent with a request. Where the server connects to (the remote policy
+    /// and the proxy) and which files it records to are left to the server config, so requests
+    /// cannot widen them. The fetch mode can only get stricter, e.g. `online` to `offline`, and
+    /// HTTP limits above the server's are capped at them.
+    pub fn with_request_overrides(&self, overrides: &Value) -> Result<Self, CompileError> {
+        let mut config = self.with_overrides(overrides)?;
+        config.http.cap_limits(&self.http);
+        let http = &config.http;
+        if config.remote != self.remote
+            || http.client != self.http.client
+            || http.recordings != self.http.recordings
+            || http.proxy != self.http.proxy
+            || http.no_proxy != self.http.no_proxy
+            || http.proxy_username != self.http.proxy_username
+            || http.proxy_password != self.http.proxy_password
+        {
+            return Err(CompileError::BadConfig(
+                "`remote`, the HTTP client, recordings and proxy settings can only be set in the server config".to_string(),
+            ));
+        }
+        if http.mode < self.http.mode {
+            return Err(CompileError::BadConfig(format!(
+                "`http.mode` can only be made stricter than `{}`",
+                format!("{:?}", self.http.mode).to_lowercase(),
+            )));
+        }
+        Ok(config)
+    }
+
+    /// The id presets of the mode with the overrides of `plugins` applied: production uses
+    /// deterministic ids, which stay stable across builds, development readable ones.
+    pub fn plugin_presets(&self) -> IdsPresets {
+        let production = self.mode == ModeConfig::Production;
+        IdsPresets {
+            module_ids: self.plugins.module_ids.unwrap_or(if production { IdsPreset::Deterministic } else { IdsPreset::Named }),
+            chunk_ids: self.plugins.chunk_ids.unwrap_or(if production { IdsPreset::Deterministic } else { IdsPreset::Natural }),
+        }
+    }
+
+    /// The optimizations of the mode with the overrides of `optimization` applied.
+    pub fn optimization(&self) -> Optimizations {
+        let production = self.mode == ModeConfig::Production;
+        let enabled = |flag: Option<bool>| flag.unwrap_or(production);
+        let overrides = &self.optimization;
+        let used_exports = enabled(overrides.used_exports);
+        let side_effects = enabled(overrides.side_effects);
+        let concatenate_modules = enabled(overrides.concatenate_modules);
+        let mangle_exports = enabled(overrides.mangle_exports);
+        Optimizations {
+            provided_exports: enabled(overrides.provided_exports)
+                || used_exports
+                || side_effects
+                || concatenate_modules
+                || mangle_exports,
+            used_exports,
+            side_effects,
+            concatenate_modules,
+            mangle_exports,
+            minimize: enabled(overrides.minimize),
+        }
+    }
+
+    /// Library types the output format and the entries use, each needs its library plugin.
+    pub fn library_types(&self) -> Vec<String> {
+        let mut types: Vec<String> = self
+            .entries
+            .values()
+            .filter_map(|entry| entry.library.as_ref())
+            .map(|library| library.library_type.clone())
+            .chain(self.output.format.library_type().map(str::to_string))
+            .collect();
+        types.sort();
+        types.dedup();
+        types
+    }
+
+    pub fn to_compiler_options(&self, context: &str, output_path: Utf8PathBuf) -> Result<CompilerOptions, CompileError> {
+        let output = &self.output;
+        let parser = &self.parser;
+        let optimization = self.optimization();
+        if output.format == OutputFormat::Umd && output.library_name.is_none() {
+            return Err(CompileError::BadConfig("the `umd` format needs `output.libraryName`".to_string()));
+        }
+
+        Ok(CompilerOptions {
+            context: context.into(),
+            dev_server: DevServerOptions { hot: self.hot },
+            output: OutputOptions {
+                path: output_path,
+                pathinfo: PathInfo::Bool(false),
+                clean: false,
+                public_path: public_path(&output.public_path),
+                asset_module_filename: Filename::from(output.asset_module_filename.clone()),
+                wasm_loading: WasmLoading::Disable,
+                webassembly_module_filename: Filename::from(String::from("webassembly.js")),
+                unique_name: output.unique_name.clone(),
+                chunk_loading: ChunkLoading::Enable(output.format.chunk_loading()),
+                chunk_loading_global: String::new(),
+                filename: Filename::from(output.filename.clone()),
+                chunk_filename: Filename::from(output.chunk_filename.clone()),
+                cross_origin_loading: CrossOriginLoading::Disable,
+                css_filename: Filename::from(output.css_filename.clone()),
+                css_chunk_filename: Filename::from(output.css_chunk_filename.clone()),
+                hot_update_main_filename: Filename::from(output.hot_update_main_filename.clone()),
+                hot_update_chunk_filename: Filename::from(output.hot_update_chunk_filename.clone()),
+                hot_update_global: format!("webpackHotUpdate{}", output.unique_name),
+                library: output.format.library_type().map(|library_type| {
+                    LibraryConfig {
+                        library_type: library_type.to_string(),
+                        name: output.library_name.clone(),
+                        export: None,
+                    }
+                    .to_library_options()
+                }),
+                enabled_library_types: Some(self.library_types()),
+                strict_module_error_handling: false,
+                global_object: output.global_object.clone(),
+                import_function_name: String::from("import"),
+                import_meta_name: String::from("import.meta"),
+                iife: false,
+                module: output.format == OutputFormat::Esm,
+                trusted_types: None,
+                source_map_filename: Filename::from(output.source_map_filename.clone()),
+                hash_function: match output.hash_function {
+                    HashFunctionConfig::Md4 => HashFunction::MD4,
+                    HashFunctionConfig::Xxhash64 => HashFunction::Xxhash64,
+                },
+                hash_digest: HashDigest::Hex,
+                hash_digest_length: output.hash_digest_length,
+                hash_salt: output.hash_salt.clone().map_or(HashSalt::None, HashSalt::Salt),
+                async_chunks: false,
+                worker_chunk_loading: ChunkLoading::Disable,
+                worker_wasm_loading: WasmLoading::Disable,
+                worker_public_path: String::new(),
+                script_type: match output.format {
+                    OutputFormat::Script => output.script_type.clone(),
+                    OutputFormat::Esm => "module".to_string(),
+                    // Classic scripts, loaded without a `type`
+                    OutputFormat::Commonjs | OutputFormat::Umd | OutputFormat::System => String::new(),
+                },
+                environment: Environment {
+                    r#const: Some(true),
+                    arrow_function: Some(true),
+                },
+                charset: false,
+                chunk_load_timeout: output.chunk_load_timeout,
+                css_head_data_compression: false,
+            },
+            target: Target::new(&self.target).map_err(|err| CompileError::BadConfig(err.to_string()))?,
+            mode: match self.mode {
+                ModeConfig::Development => Mode::Development,
+                ModeConfig::Production => Mode::Production,
+                ModeConfig::None => Mode::None,
+            },
+            resolve: Resolve {
+                extensions: Some(self.resolve.extensions.clone()),
+                ..Default::default()
+            },
+            resolve_loader: Resolve {
+                extensions: Some(self.resolve.extensions.clone()),
+                ..Default::default()
+            },
+            module: ModuleOptions {
+                parser: Some(ParserOptionsMap::from_iter([(
+                    ModuleType::JsAuto.to_string(),
+                    ParserOptions::Javascript(JavascriptParserOptions {
+                        dynamic_import_mode: Some(match parser.dynamic_import_mode {
+                            DynamicImportModeConfig::Eager => DynamicImportMode::Eager,
+                            DynamicImportModeConfig::Lazy => DynamicImportMode::Lazy,
+                            DynamicImportModeConfig::LazyOnce => DynamicImportMode::LazyOnce,
+                            DynamicImportModeConfig::Weak => DynamicImportMode::Weak,
+                        }),
+                        dynamic_import_prefetch: parser.dynamic_import_prefetch.map(JavascriptParserOrder::Order),
+                        import_meta: Some(parser.import_meta),
+                        dynamic_import_fetch_priority: Some(DynamicImportFetchPriority::Auto),
+                        url: Some(if parser.url {
+                            JavascriptParserUrl::Enable
+                        } else {
+                            JavascriptParserUrl::Disable
+                        }),
+                        expr_context_critical: Some(parser.expr_context_critical),
+                        wrapped_context_critical: Some(parser.wrapped_context_critical),
+                        exports_presence: None,
+                        import_exports_presence: None,
+                        reexport_exports_presence: None,
+                        strict_export_presence: Some(parser.strict_export_presence),
+                        worker: Some(vec![]),
+                        dynamic_import_preload: parser.dynamic_import_preload.map(JavascriptParserOrder::Order),
+                        override_strict: None,
+                    }),
+                )])),
+                ..Default::default()
+            },
+            stats: StatsOptions::default(),
+            snapshot: SnapshotOptions,
+            cache: CacheOptions::default(),
+            experiments: Experiments {
+                layers: false,
+                incremental: if self.incremental {
+                    Incremental::Enabled {
+                        make: true,
+                        emit_assets: true,
+                    }
+                } else {
+                    Incremental::Disabled
+                },
+                top_level_await: false,
+                rspack_future: RspackFuture {},
+            },
+            optimization: Optimization {
+                concatenate_modules: optimization.concatenate_modules,
+                remove_available_modules: false,
+                provided_exports: optimization.provided_exports,
+                mangle_exports: if optimization.mangle_exports {
+                    MangleExportsOption::Deterministic
+                } else {
+                    MangleExportsOption::False
+                },
+                inner_graph: true,
+                used_exports: if optimization.used_exports {
+                    UsedExportsOption::True
+                } else {
+                    UsedExportsOption::default()
+                },
+                side_effects: if optimization.side_effects {
+                    SideEffectOption::True
+                } else {
+                    SideEffectOption::default()
+                },
+            },
+            profile: false,
+            bail: false,
+            __references: Map::<String, Value>::new(),
+            node: None,
+        })
+    }
+}
+
+impl EntryConfig {
+    pub fn to_entry_options(&self, name: &str) -> EntryOptions {
+        EntryOptions {
+            name: Some(name.to_string()),
+            runtime: self.runtime.clone().map(EntryRuntime::String),
+            layer: None,
+            chunk_loading: None,
+            async_chunks: None,
+            public_path: self.public_path.as_deref().map(public_path),
+            base_uri: None,
+            filename: self.filename.clone().map(Filename::from),
+            library: self.library.as_ref().map(LibraryConfig::to_library_options),
+            depend_on: (!self.depend_on.is_empty()).then(|| self.depend_on.clone()),
+        }
+    }
+}
+
+impl LibraryConfig {
+    pub fn to_library_options(&self) -> LibraryOptions {
+        LibraryOptions {
+            name: self.name.clone().map(|name| LibraryName::NonUmdObject(LibraryNonUmdObject::String(name))),
+            export: self.export.clone(),
+            library_type: self.library_type.clone(),
+            umd_named_define: None,
+            auxiliary_comment: None,
+            amd_container: None,
+        }
+    }
+}
+
+// `"auto"` or a literal prefix
+fn public_path(value: &str) -> PublicPath {
+    if value == "auto" {
+        PublicPath::Auto
+    } else {
+        PublicPath::Filename(Filename::from(value.to_string()))
+    }
+}
+
+fn merge_json(base: &mut Value, patch: &Value) {
+    match (base, patch) {
+        (Value::Object(base), Value::Object(patch)) => {
+            for (key, value) in patch {
+                merge_json(base.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (base, patch) => *base = patch.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn server() -> EdgeCompileConfig {
+        let mut config = EdgeCompileConfig::default();
+        config.http.mode = FetchMode::Frozen;
+        config.http.proxy = Some("http://proxy.internal:3128".to_string());
+        config
+    }
+
+    #[test]
+    fn requests_cannot_change_where_the_server_connects_to() {
+        let rejected = [
+            json!({ "remote": { "allowPrivateNetworks": true } }),
+            json!({ "remote": { "allow": ["http://"] } }),
+            json!({ "http": { "proxy": "http://attacker.example:8080" } }),
+            json!({ "http": { "proxy": null } }),
+            json!({ "http": { "noProxy": "*" } }),
+            json!({ "http": { "proxyUsername": "user", "proxyPassword": "secret" } }),
+            json!({ "http": { "client": "record" } }),
+            json!({ "http": { "recordings": "/etc" } }),
+        ];
+        for overrides in rejected {
+            let result = server().with_request_overrides(&overrides);
+            assert!(matches!(result, Err(CompileError::BadConfig(_))), "{} should be rejected", overrides);
+        }
+        // Restating the server's values is not a change
+        let same = json!({ "http": { "proxy": "http://proxy.internal:3128", "client": "network" } });
+        assert!(server().with_request_overrides(&same).is_ok());
+    }
+
+    #[test]
+    fn requests_can_only_make_the_fetch_mode_stricter() {
+        let mode = |mode: &str| server().with_request_overrides(&json!({ "http": { "mode": mode } }));
+        assert!(matches!(mode("online"), Err(CompileError::BadConfig(_))));
+        assert_eq!(mode("frozen").unwrap().http.mode, FetchMode::Frozen);
+        assert_eq!(mode("offline").unwrap().http.mode, FetchMode::Offline);
+    }
+
+    #[test]
+    fn request_limits_are_capped_at_the_servers() {
+        let overrides = json!({
+            "http": {
+                "connectTimeoutMs": u64::MAX,
+                "timeoutMs": u64::MAX,
+                "retries": 100,
+                "retryBackoffMs": u64::MAX,
+                "maxRetryDelayMs": u64::MAX,
+                "maxResponseSize": usize::MAX,
+                "maxRedirects": 1000,
+            }
+        });
+        let config = server().with_request_overrides(&overrides).unwrap();
+        assert_eq!(config.http, server().http);
+
+        let lower = json!({ "http": { "timeoutMs": 1000, "retries": 0, "maxRedirects": 1 } });
+        let config = server().with_request_overrides(&lower).unwrap();
+        assert_eq!(config.http.timeout_ms, 1000);
+        assert_eq!(config.http.retries, 0);
+        assert_eq!(config.http.max_redirects, 1);
+    }
+}
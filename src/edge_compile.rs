@@ -1,165 +1,111 @@
 use std::sync::Arc;
+use std::sync::{OnceLock, PoisonError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use std::path::Path;
-use std::collections::HashMap;
-use rspack_ids::{NaturalChunkIdsPlugin, NamedModuleIdsPlugin};
+use std::path::{Component, Path, PathBuf};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use rspack_ids::{
+    DeterministicChunkIdsPlugin, DeterministicModuleIdsPlugin, NamedChunkIdsPlugin, NamedModuleIdsPlugin,
+    NaturalChunkIdsPlugin, NaturalModuleIdsPlugin,
+};
 use rspack_core::{
-    ResolverFactory,
-    CacheOptions, ChunkLoading, ChunkLoadingType, Compiler, CompilerOptions, Context,
-    CrossOriginLoading, DevServerOptions, EntryOptions, Environment, Experiments, Filename,
-    HashDigest, HashFunction, HashSalt, MangleExportsOption, Mode,
-    ModuleOptions, Optimization, OutputOptions, PathInfo,
-    Plugin, PublicPath, Resolve, SideEffectOption, SnapshotOptions,
-    StatsOptions, Target, UsedExportsOption, WasmLoading,
-    DynamicImportMode, DynamicImportFetchPriority, JavascriptParserOrder, JavascriptParserUrl,
-    ParserOptionsMap, ModuleType, ParserOptions, JavascriptParserOptions,RspackFuture,Incremental
+    ResolverFactory, Compiler, Context, EntryOptions, Plugin,
 };
 use rspack_plugin_entry::EntryPlugin;
-use rspack_plugin_javascript::JsPlugin;
+use rspack_plugin_hmr::HotModuleReplacementPlugin;
+use rspack_plugin_javascript::{
+    FlagDependencyExportsPlugin, FlagDependencyUsagePlugin, JsPlugin, MangleExportsPlugin, ModuleConcatenationPlugin,
+    SideEffectsFlagPlugin,
+};
+use rspack_plugin_swc_js_minimizer::{SwcJsMinimizerRspackPlugin, SwcJsMinimizerRspackPluginOptions};
+use rspack_plugin_runtime::{
+    enable_chunk_loading_plugin, ArrayPushCallbackChunkFormatPlugin, CommonJsChunkFormatPlugin, ModuleChunkFormatPlugin,
+};
 use rspack_plugin_schemes::{
-    DataUriPlugin, HttpUriPlugin, HttpUriPluginOptions, HttpUriOptionsAllowedUris
+    DataUriPlugin, HttpClient, HttpUriPlugin, HttpUriPluginOptions, HttpUriOptionsAllowedUris
 };
-use serde_json::{Map, Value};
-use std::fs;
-use crate::memory_fs::MockFileSystem;
+use crate::bundle_store::is_hot_update;
+use crate::memory_fs::{FileKind, MockFileSystem};
 use crate::system_fs::RealFileSystem;
 use rspack_fs::AsyncFileSystem;
-use crate::http_io::ReqwestHttpClient;
+use crate::http_cache::{http_cache_location, CachingHttpClient};
+use crate::http_fixtures::{FixtureHttpClient, RecordingHttpClient};
+use crate::http_io::{FetchReport, RedirectChain, ReqwestHttpClient};
+use crate::integrity::IntegrityHttpClient;
 use rspack_paths::{Utf8PathBuf};
-use rspack_fs::ReadableFileSystem;
+use rspack_fs::{ReadableFileSystem, WritableFileSystem};
+use crate::compile_error::{CompileDiagnostic, CompileError};
+use crate::config::{EdgeCompileConfig, FetchMode, HttpClientMode, IdsPreset, IdsPresets, OutputFormat};
+use crate::remote_policy::{PolicyHttpClient, RemotePolicy};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 
-pub async fn compile(network_entry: Option<String>) -> HashMap<String, Vec<u8>> {
-    let mock_fs = MockFileSystem::new();
-    let output_filesystem = mock_fs.clone();
-    let root = env!("CARGO_MANIFEST_DIR");
-    let context = Context::new(root.to_string().into());
-    let dist_dir: Utf8PathBuf = Utf8PathBuf::from_path_buf(Path::new(root).join("./dist")).unwrap();
-    if !dist_dir.exists() {
-        fs::create_dir_all(&dist_dir).expect("Failed to create dist directory");
-    }
-    let dist_dir = Utf8PathBuf::from_path_buf(dist_dir.canonicalize().unwrap()).unwrap();
-    let entry_file: String = network_entry
-        .as_deref()
-        .filter(|entry| !entry.is_empty())
-        .map_or_else(
-            || {
-                Utf8PathBuf::from_path_buf(Path::new(root)
-                    .join("./fixtures/index.js")
-                    .canonicalize()
-                    .unwrap()).unwrap()
-                    .to_string()
-            },
-            |entry| entry.to_string(),
-        );
-    dbg!(network_entry.clone());
-
-    let compiler_options = CompilerOptions {
-        context: root.into(),
-        dev_server: DevServerOptions::default(),
-        output: OutputOptions {
-            path: dist_dir,
-            pathinfo: PathInfo::Bool(false),
-            clean: false,
-            public_path: PublicPath::Auto,
-            asset_module_filename: Filename::from(String::from("asset-[name].js")),
-            wasm_loading: WasmLoading::Disable,
-            webassembly_module_filename: Filename::from(String::from("webassembly.js")),
-            unique_name: "main".into(),
-            chunk_loading: ChunkLoading::Enable(ChunkLoadingType::Jsonp),
-            chunk_loading_global: String::new(),
-            filename: Filename::from(String::from("[name].js")),
-            chunk_filename: Filename::from(String::from("[id].js")),
-            cross_origin_loading: CrossOriginLoading::Disable,
-            css_filename: Filename::from(String::from("[name].css")),
-            css_chunk_filename: Filename::from(String::from("[id].css")),
-            hot_update_main_filename: Filename::from(String::from("[name].[hash].hot-update.js")),
-            hot_update_chunk_filename: Filename::from(String::from("[id].[hash].hot-update.js")),
-            hot_update_global: String::new(),
-            library: None,
-            enabled_library_types: None,
-            strict_module_error_handling: false,
-            global_object: String::from("window"),
-            import_function_name: String::from("import"),
-            import_meta_name: String::from("import.meta"),
-            iife: false,
-            module: false,
-            trusted_types: None,
-            source_map_filename: Filename::from(String::from("[file].map")),
-            hash_function: HashFunction::MD4,
-            hash_digest: HashDigest::Hex,
-            hash_digest_length: 20,
-            hash_salt: HashSalt::Salt(String::from("salt")),
-            async_chunks: false,
-            worker_chunk_loading: ChunkLoading::Disable,
-            worker_wasm_loading: WasmLoading::Disable,
-            worker_public_path: String::new(),
-            script_type: String::from("javascript/module"),
-            environment: Environment {
-                r#const: Some(true),
-                arrow_function: Some(true),
-            },
-            charset: false,
-            chunk_load_timeout: 120000,
-            css_head_data_compression: false,
-        },
-        target: Target::new(&vec!["es2022".to_string()]).unwrap(),
-        mode: Mode::Development,
-        resolve: Resolve {
-            extensions: Some(vec![".js".to_string()]),
-            ..Default::default()
-        },
-        resolve_loader: Resolve {
-            extensions: Some(vec![".js".to_string()]),
-            ..Default::default()
-        },
-        module: ModuleOptions {
-            parser: Some(ParserOptionsMap::from_iter([(
-                ModuleType::JsAuto.to_string(),
-                ParserOptions::Javascript(JavascriptParserOptions {
-                    dynamic_import_mode: Some(DynamicImportMode::Eager),
-                    dynamic_import_prefetch: Some(JavascriptParserOrder::Order(1)),
-                    import_meta: Some(false),
-                    dynamic_import_fetch_priority: Some(DynamicImportFetchPriority::Auto),
-                    url: Some(JavascriptParserUrl::Disable),
-                    expr_context_critical: Some(false),
-                    wrapped_context_critical: Some(false),
-                    exports_presence: None,
-                    import_exports_presence: None,
-                    reexport_exports_presence: None,
-                    strict_export_presence: Some(false),
-                    worker: Some(vec![]),
-                    dynamic_import_preload: Some(JavascriptParserOrder::Order(0)),
-                    override_strict: None,
-                }),
-            )])),
-            ..Default::default()
-        },
-        stats: StatsOptions::default(),
-        snapshot: SnapshotOptions,
-        cache: CacheOptions::default(),
-        experiments: Experiments {
-            layers: false,
-            incremental: Incremental::Disabled,
-            top_level_await: false,
-            rspack_future: RspackFuture {},
-        },
-        optimization: Optimization {
-            concatenate_modules: false,
-            remove_available_modules: false,
-            provided_exports: false,
-            mangle_exports: MangleExportsOption::False,
-            inner_graph: true,
-            used_exports: UsedExportsOption::default(),
-            side_effects: SideEffectOption::default(),
-        },
-        profile: false,
-        bail: false,
-        __references: Map::<String, Value>::new(),
-        node: None,
-    };
-    let mut plugins: Vec<Box<dyn Plugin>> = Vec::new();
+/// Directory the sources of a [`CompileInput::Virtual`] compilation are mounted under.
+pub const VIRTUAL_ROOT: &str = "/__virtual";
+
+/// Parent of the per-compilation output roots in the in-memory output filesystem.
+pub const OUTPUT_ROOT: &str = "/__out";
+
+// Client runtime added to hot compilations, see `hot_client_entry`
+const HMR_CLIENT: &str = include_str!("hmr_client.js");
+
+/// Returns an id unique to this compilation, used for its output root `/__out/<id>`.
+///
+/// Ids carry the server start time so they do not repeat across restarts.
+pub fn next_compilation_id() -> String {
+    static BOOT: OnceLock<u64> = OnceLock::new();
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    let boot = BOOT.get_or_init(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+    });
+    format!("{:x}-{}", boot, NEXT_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+/// What to compile.
+pub enum CompileInput {
+    /// An entry request resolved against the server: a local path, a remote URL or a data URI.
+    /// `None` or an empty string compiles `fixtures/index.js` from the working directory.
+    Entry(Option<String>),
+    /// Sources submitted by the client, compiled without touching the disk.
+    Virtual(VirtualSources),
+}
+
+/// An in-memory file tree (relative path to content) and the entry inside it.
+pub struct VirtualSources {
+    /// Empty when only the entries of the config are compiled.
+    pub entry: String,
+    pub files: BTreeMap<String, Vec<u8>>,
+}
+
+impl VirtualSources {
+    /// Mounts the tree under [`VIRTUAL_ROOT`].
+    fn mount(self, fs: &MockFileSystem) -> Result<(), CompileError> {
+        let root = Path::new(VIRTUAL_ROOT);
+        for (path, content) in self.files {
+            let relative = normalize_virtual_path(&path)
+                .ok_or_else(|| CompileError::BadRequest(format!("invalid source path `{}`", path)))?;
+            fs.insert(&root.join(relative), content);
+        }
+        Ok(())
+    }
+}
 
-    let entry_plugin_options = EntryOptions {
+/// Absolute path of the entry `request` in mounted sources, which must be one of the files.
+fn virtual_entry(fs: &MockFileSystem, request: &str) -> Result<String, CompileError> {
+    normalize_virtual_path(request)
+        .map(|relative| Path::new(VIRTUAL_ROOT).join(relative))
+        .filter(|entry| fs.stat(entry).is_some_and(|stat| stat.kind == FileKind::File))
+        .map(|entry| entry.to_string_lossy().to_string())
+        .ok_or_else(|| CompileError::BadEntry {
+            entry: request.to_string(),
+            reason: "entry is not one of the submitted files".to_string(),
+        })
+}
+
+// The entry a request names itself, with the output defaults.
+fn main_entry(request: String) -> (String, String, EntryOptions) {
+    let options = EntryOptions {
         name: Some("main".to_string()),
         runtime: None,
         layer: None,
@@ -171,82 +117,700 @@ pub async fn compile(network_entry: Option<String>) -> HashMap<String, Vec<u8>>
         library: None,
         depend_on: None,
     };
-    let entry_plugin = Box::new(EntryPlugin::new(context, entry_file.clone(), entry_plugin_options));
-    plugins.push(Box::new(JsPlugin::default()));
-    plugins.push(entry_plugin);
-    plugins.push(Box::new(NaturalChunkIdsPlugin::default()));
-    plugins.push(Box::new(NamedModuleIdsPlugin::default()));
-    plugins.push(Box::new(DataUriPlugin::default()));
+    ("main".to_string(), request, options)
+}
+
+// Accepts `a/b.js` and `./a/b.js`; rejects absolute paths and anything climbing out with `..`.
+fn normalize_virtual_path(path: &str) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!normalized.as_os_str().is_empty()).then_some(normalized)
+}
+
+/// Everything a successful compilation hands back to the caller.
+pub struct CompileOutput {
+    /// Unique id of this compilation, see [`next_compilation_id`].
+    pub id: String,
+    /// Id of the compiler that produced this output; stays the same across rebuilds.
+    pub compiler_id: String,
+    /// Full hash of the compilation, which hot clients compare against their own.
+    pub hash: Option<String>,
+    /// The entry request the compiler was actually run with; with named entries, the one of
+    /// `main` or else of the first entry.
+    pub entry: String,
+    /// Every entry by name, with the request it was run with.
+    pub entries: BTreeMap<String, String>,
+    /// Emitted assets keyed by their path relative to the output root.
+    pub files: HashMap<String, Vec<u8>>,
+    pub warnings: Vec<CompileDiagnostic>,
+    /// Files the compilation read, for cache invalidation.
+    pub file_dependencies: Vec<PathBuf>,
+    /// Directories whose listing the compilation depended on.
+    pub context_dependencies: Vec<PathBuf>,
+    /// URLs of the remote modules that ended up in the module graph.
+    pub remote_modules: Vec<String>,
+    /// Redirects followed while fetching remote modules for this build.
+    pub redirects: Vec<RedirectChain>,
+}
+
+/// Returns the request the compiler will use for `network_entry`, falling back to the fixture under `root`.
+fn resolve_entry(root: &str, network_entry: Option<&str>, input_fs: &dyn ReadableFileSystem) -> Result<String, CompileError> {
+    let Some(entry) = network_entry.filter(|entry| !entry.is_empty()) else {
+        let fixture = Path::new(root).join("./fixtures/index.js");
+        return fixture
+            .canonicalize()
+            .ok()
+            .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+            .map(|path| path.to_string())
+            .ok_or_else(|| CompileError::BadEntry {
+                entry: fixture.display().to_string(),
+                reason: "default fixture entry is missing".to_string(),
+            });
+    };
 
+    let bad_entry = |reason: &str| CompileError::BadEntry {
+        entry: entry.to_string(),
+        reason: reason.to_string(),
+    };
+    if entry.starts_with("http://") || entry.starts_with("https://") {
+        url::Url::parse(entry).map_err(|err| bad_entry(&err.to_string()))?;
+    } else if entry.starts_with("data:") {
+        // Data URIs are validated by DataUriPlugin itself.
+    } else if let Some(url) = url::Url::parse(entry).ok().filter(|url| url.scheme().len() > 1) {
+        // Single-letter schemes are Windows drive letters, not URLs.
+        return Err(bad_entry(&format!("unsupported scheme `{}`", url.scheme())));
+    } else if entry.starts_with('/') || entry.starts_with("./") || entry.starts_with("../") {
+        // Bare specifiers are left to the resolver, explicit paths must exist up front.
+        let is_file = input_fs.metadata(&Path::new(root).join(entry)).is_ok_and(|metadata| metadata.is_file());
+        if !is_file {
+            return Err(bad_entry("file does not exist"));
+        }
+    }
+    Ok(entry.to_string())
+}
+
+/// Directory `HttpUriPlugin` caches remote modules in, next to the closest `package.json` above `cwd`.
+pub fn cache_location(cwd: &Path) -> PathBuf {
+    let mut dir = cwd.to_path_buf();
+    loop {
+        if let Ok(metadata) = std::fs::metadata(dir.join("package.json")) {
+            if metadata.is_file() {
+                break;
+            }
+        }
+        let parent = dir.parent();
+        if parent.is_none() {
+            dir = cwd.join(".cache/webpack");
+            break;
+        }
+        dir = parent.unwrap().to_path_buf();
+    }
+    if std::env::var("pnp").unwrap_or_default() == "1" {
+        dir.join(".pnp/.cache/webpack")
+    } else if std::env::var("pnp").unwrap_or_default() == "3" {
+        dir.join(".yarn/.cache/webpack")
+    } else {
+        dir.join("node_modules/.cache/webpack")
+    }
+}
+
+pub fn lockfile_location(cwd: &Path) -> PathBuf {
+    cache_location(cwd).join("lockfile.json")
+}
+
+/// A compiler set up for one input and config. It is built once and can then be rebuilt
+/// incrementally, which reuses the module graph of the previous build.
+pub struct PreparedCompilation {
+    entry: String,
+    entries: BTreeMap<String, String>,
+    root_id: String,
+    builds: usize,
+    compiler: Compiler,
+    /// Why remote modules failed to fetch and where they redirected, drained after every build.
+    fetch_report: Arc<FetchReport>,
+    /// Whether the build may not change the lockfile, see [`CompileError::from_diagnostics`].
+    locked: bool,
+    output_filesystem: MockFileSystem,
+    output_root: Utf8PathBuf,
+    /// Embedder filesystem and directory every build's assets are copied to.
+    emit_to: Option<(Arc<dyn WritableFileSystem + Send + Sync>, Utf8PathBuf)>,
+}
+
+/// Creates a plugin for every compilation it is registered with, since plugins are owned by
+/// the compiler they are added to.
+pub type PluginFactory = Arc<dyn Fn() -> Box<dyn Plugin> + Send + Sync>;
+
+/// Built-in plugins that can be replaced or left out, see
+/// [`crate::EdgeCompilerBuilder::replace_plugin`]. The entry, JavaScript, HTTP and HMR plugins
+/// are not among them since the compilation itself relies on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DefaultPlugin {
+    /// Picked by `plugins.moduleIds`; named ids by default, deterministic in production.
+    ModuleIds,
+    /// Picked by `plugins.chunkIds`; natural ids by default, deterministic in production.
+    ChunkIds,
+    /// Loads `data:` entries and imports; hot compilations need it for their client.
+    DataUri,
+}
+
+impl DefaultPlugin {
+    const ALL: [DefaultPlugin; 3] = [DefaultPlugin::ChunkIds, DefaultPlugin::ModuleIds, DefaultPlugin::DataUri];
+
+    /// Creates the built-in plugin, with the ids `presets` picks for the id plugins.
+    fn create(self, presets: IdsPresets) -> Box<dyn Plugin> {
+        match (self, presets.module_ids, presets.chunk_ids) {
+            (DefaultPlugin::ModuleIds, IdsPreset::Named, _) => Box::new(NamedModuleIdsPlugin::default()),
+            (DefaultPlugin::ModuleIds, IdsPreset::Natural, _) => Box::new(NaturalModuleIdsPlugin::default()),
+            (DefaultPlugin::ModuleIds, IdsPreset::Deterministic, _) => Box::new(DeterministicModuleIdsPlugin::default()),
+            (DefaultPlugin::ChunkIds, _, IdsPreset::Named) => Box::new(NamedChunkIdsPlugin::new(None, None)),
+            (DefaultPlugin::ChunkIds, _, IdsPreset::Natural) => Box::new(NaturalChunkIdsPlugin::default()),
+            (DefaultPlugin::ChunkIds, _, IdsPreset::Deterministic) => Box::new(DeterministicChunkIdsPlugin::default()),
+            (DefaultPlugin::DataUri, _, _) => Box::new(DataUriPlugin::default()),
+        }
+    }
+}
+
+/// The parts of a compilation an embedder can swap out, see [`crate::EdgeCompiler`]. Every
+/// `None` keeps what the server uses.
+#[derive(Clone, Default)]
+pub struct CompileEnv {
+    /// Directory local entries, the HTTP cache, the lockfile and recordings resolve against
+    /// instead of the current directory.
+    pub context: Option<PathBuf>,
+    /// Where [`CompileInput::Entry`] sources are read from instead of the disk.
+    pub input_filesystem: Option<Arc<dyn ReadableFileSystem + Send + Sync>>,
+    /// Receives every emitted asset below the given directory, in addition to
+    /// [`CompileOutput::files`].
+    pub output_filesystem: Option<(Arc<dyn WritableFileSystem + Send + Sync>, Utf8PathBuf)>,
+    /// Fetches remote modules instead of the network, HTTP cache and recordings the config
+    /// selects. The remote policy and integrity checks still apply.
+    pub http_client: Option<Arc<dyn HttpClient>>,
+    /// Added after the built-in plugins.
+    pub plugins: Vec<PluginFactory>,
+    /// Built-in plugins created by the given factory instead, or left out for `None`.
+    pub replaced_plugins: HashMap<DefaultPlugin, Option<PluginFactory>>,
+}
+
+/// Compiles `input` once from scratch.
+pub async fn compile(input: CompileInput, config: &EdgeCompileConfig) -> Result<CompileOutput, CompileError> {
+    prepare(input, config).await?.build().await
+}
+
+/// Creates the compiler for `input` without running it.
+pub async fn prepare(input: CompileInput, config: &EdgeCompileConfig) -> Result<PreparedCompilation, CompileError> {
+    prepare_with(input, config, &CompileEnv::default()).await
+}
+
+/// Like [`prepare`], with the parts `env` replaces.
+pub async fn prepare_with(input: CompileInput, config: &EdgeCompileConfig, env: &CompileEnv) -> Result<PreparedCompilation, CompileError> {
+    let mock_fs = MockFileSystem::new();
+    let output_filesystem = mock_fs.clone();
+    // Local entries resolve against the directory the server runs in
+    let cwd = match &env.context {
+        Some(context) => context.clone(),
+        None => std::env::current_dir()
+            .map_err(|err| CompileError::Internal(format!("failed to read current directory: {}", err)))?,
+    };
+    let root = cwd.to_str()
+        .ok_or_else(|| CompileError::Internal("context directory is not valid UTF-8".to_string()))?;
     let real_fs = Arc::new(RealFileSystem::new());
     let native_fs_async: Arc<dyn AsyncFileSystem + Send + Sync> = real_fs.clone();
-    let native_fs_read: Arc<dyn ReadableFileSystem + Send + Sync> = real_fs.clone();
-
-    let cache_location = Some({
-        let cwd = std::env::current_dir().unwrap();
-        let mut dir = cwd.clone();
-        loop {
-            if let Ok(metadata) = std::fs::metadata(dir.join("package.json")) {
-                if metadata.is_file() {
-                    break;
-                }
+    let entry_fs: Arc<dyn ReadableFileSystem + Send + Sync> = match &env.input_filesystem {
+        Some(input_filesystem) => input_filesystem.clone(),
+        None => real_fs.clone(),
+    };
+
+    let policy = Arc::new(RemotePolicy::new(&config.remote)?);
+
+    // Virtual sources get their own in-memory input filesystem rooted at VIRTUAL_ROOT. The
+    // request's own entry is optional once the config names entries.
+    let named_entries = !config.entries.is_empty();
+    let (context_root, input_fs, mut entries): (&str, Arc<dyn ReadableFileSystem + Send + Sync>, Vec<(String, String, EntryOptions)>) = match input {
+        CompileInput::Entry(network_entry) => {
+            let mut entries = Vec::new();
+            if !(named_entries && network_entry.as_deref().unwrap_or_default().is_empty()) {
+                let request = resolve_entry(root, network_entry.as_deref(), entry_fs.as_ref())?;
+                entries.push(main_entry(request));
             }
-            let parent = dir.parent();
-            if parent.is_none() {
-                dir = cwd.join(".cache/webpack");
-                break;
+            for (name, entry) in &config.entries {
+                let request = resolve_entry(root, Some(&entry.import), entry_fs.as_ref())?;
+                entries.push((name.clone(), request, entry.to_entry_options(name)));
+            }
+            (root, entry_fs, entries)
+        }
+        CompileInput::Virtual(sources) => {
+            let input_fs = MockFileSystem::new();
+            let main = sources.entry.clone();
+            sources.mount(&input_fs)?;
+            let mut entries = Vec::new();
+            if !(named_entries && main.is_empty()) {
+                entries.push(main_entry(virtual_entry(&input_fs, &main)?));
             }
-            dir = parent.unwrap().to_path_buf();
+            for (name, entry) in &config.entries {
+                let request = virtual_entry(&input_fs, &entry.import)?;
+                entries.push((name.clone(), request, entry.to_entry_options(name)));
+            }
+            (VIRTUAL_ROOT, Arc::new(input_fs), entries)
         }
-        if std::env::var("pnp").unwrap_or_default() == "1" {
-            dir.join(".pnp/.cache/webpack")
-        } else if std::env::var("pnp").unwrap_or_default() == "3" {
-            dir.join(".yarn/.cache/webpack")
-        } else {
-            dir.join("node_modules/.cache/webpack")
+    };
+    let mut names = HashSet::new();
+    for (name, request, _) in &entries {
+        if !names.insert(name.as_str()) {
+            return Err(CompileError::BadConfig(format!("entry `{}` is also the name of the request's entry", name)));
         }
-        .to_string_lossy()
-        .to_string()
+        policy.check_entry(request)?;
+    }
+    // Every compilation writes below its own root, so concurrent builds never share paths
+    let id = next_compilation_id();
+    let output_root: Utf8PathBuf = Utf8PathBuf::from(OUTPUT_ROOT).join(&id);
+    let compiler_options = config.to_compiler_options(context_root, output_root.clone())?;
+    let mut plugins: Vec<Box<dyn Plugin>> = Vec::new();
+
+    plugins.push(Box::new(JsPlugin::default()));
+    for (_, request, options) in &entries {
+        let context = Context::new(context_root.to_string().into());
+        plugins.push(Box::new(EntryPlugin::new(context, request.clone(), options.clone())));
+    }
+    for library_type in config.library_types() {
+        rspack_plugin_library::enable_library_plugin(library_type, &mut plugins);
+    }
+    // How chunks are wrapped has to match how the runtime loads them
+    let format = config.output.format;
+    plugins.push(match format {
+        OutputFormat::Script | OutputFormat::Umd | OutputFormat::System => Box::new(ArrayPushCallbackChunkFormatPlugin::default()),
+        OutputFormat::Esm => Box::new(ModuleChunkFormatPlugin::default()),
+        OutputFormat::Commonjs => Box::new(CommonJsChunkFormatPlugin::default()),
     });
+    enable_chunk_loading_plugin(format.chunk_loading(), &mut plugins);
+    // The optimization options only take effect through the plugins implementing them
+    let optimization = config.optimization();
+    if optimization.provided_exports {
+        plugins.push(Box::new(FlagDependencyExportsPlugin::default()));
+    }
+    if optimization.used_exports {
+        plugins.push(Box::new(FlagDependencyUsagePlugin::new(false)));
+    }
+    if optimization.side_effects {
+        plugins.push(Box::new(SideEffectsFlagPlugin::default()));
+    }
+    if optimization.concatenate_modules {
+        plugins.push(Box::new(ModuleConcatenationPlugin::default()));
+    }
+    if optimization.mangle_exports {
+        plugins.push(Box::new(MangleExportsPlugin::new(true)));
+    }
+    if optimization.minimize {
+        plugins.push(Box::new(SwcJsMinimizerRspackPlugin::new(SwcJsMinimizerRspackPluginOptions::default())));
+    }
+    for default_plugin in DefaultPlugin::ALL {
+        match env.replaced_plugins.get(&default_plugin) {
+            Some(Some(factory)) => plugins.push(factory()),
+            Some(None) => {}
+            None => plugins.push(default_plugin.create(config.plugin_presets())),
+        }
+    }
+    if config.hot {
+        // The client runtime goes into each entry so it shares the module registry; entries
+        // depending on another one get it from there
+        let client_entry = hot_client_entry(&id);
+        for (name, _, options) in &entries {
+            if options.depend_on.is_some() {
+                continue;
+            }
+            let client_options = EntryOptions {
+                name: Some(name.clone()),
+                runtime: None,
+                layer: None,
+                chunk_loading: None,
+                async_chunks: None,
+                public_path: None,
+                base_uri: None,
+                filename: None,
+                library: None,
+                depend_on: None,
+            };
+            let context = Context::new(context_root.to_string().into());
+            plugins.push(Box::new(EntryPlugin::new(context, client_entry.clone(), client_options)));
+        }
+        plugins.push(Box::new(HotModuleReplacementPlugin::default()));
+    }
+
+    let cache_dir = cache_location(&cwd);
+    let cache_location = Some(cache_dir.to_string_lossy().to_string());
 
-    let lockfile_location = cache_location.clone().map(|loc| format!("{}/lockfile.json", loc));
+    let lockfile_path = lockfile_location(&cwd);
+    let lockfile_location = Some(lockfile_path.to_string_lossy().to_string());
 
-    let http_client = Arc::new(ReqwestHttpClient::new());
+    let fetch_report = Arc::new(FetchReport::default());
+    let network_client = fetch_client(config, env, &policy, &cwd, &fetch_report)?;
+    // Once locked, the lockfile is the reference; online builds may still change it
+    let locked_integrity = (config.http.mode != FetchMode::Online).then_some(lockfile_path);
+    let verified_client = IntegrityHttpClient::new(
+        network_client,
+        config.remote.integrity.clone(),
+        locked_integrity,
+        fetch_report.clone(),
+    );
+    // Every remote import goes through the policy before it is looked up in the HTTP cache
+    // or fetched
+    let http_client = Arc::new(PolicyHttpClient::new(Arc::new(verified_client), policy.clone()));
 
     let http_uri_options = HttpUriPluginOptions {
         allowed_uris: HttpUriOptionsAllowedUris,
         cache_location: cache_location.clone(),
-        frozen: Some(true),
+        // Only online builds may add to the lockfile; they also refresh stale entries
+        frozen: Some(config.http.mode != FetchMode::Online),
         lockfile_location, 
-        proxy: Some("http://proxy.example.com".to_string()),
-        upgrade: Some(true),
+        proxy: config.http.proxy.clone(),
+        upgrade: Some(config.http.mode == FetchMode::Online),
         filesystem: native_fs_async.clone(),
         http_client: Some(http_client)
     };
     plugins.push(Box::new(HttpUriPlugin::new(http_uri_options)));
+    plugins.extend(env.plugins.iter().map(|factory| factory()));
 
     let resolver_factory = Arc::new(ResolverFactory::new(
         compiler_options.resolve.clone(),
-        Arc::new(RealFileSystem::new()),
+        input_fs.clone(),
     ));
     let loader_resolver_factory = Arc::new(ResolverFactory::new(
         compiler_options.resolve_loader.clone(),
-        Arc::new(RealFileSystem::new()),
+        input_fs.clone(),
     ));
-    let mut compiler = Compiler::new(
+    let compiler = Compiler::new(
         compiler_options,
         plugins,
         Some(Box::new(output_filesystem.clone())),
-        Some(native_fs_read.clone()),
+        Some(input_fs.clone()),
         Some(resolver_factory),
         Some(loader_resolver_factory),
     );
-    println!("Compiling with entry: {}", entry_file);
-    compiler.build().await.expect("build failed");
+    Ok(PreparedCompilation {
+        entry: entries.first().map(|(_, request, _)| request.clone()).unwrap_or_default(),
+        entries: entries.into_iter().map(|(name, request, _)| (name, request)).collect(),
+        root_id: id,
+        builds: 0,
+        compiler,
+        fetch_report,
+        locked: config.http.mode != FetchMode::Online,
+        output_filesystem,
+        output_root,
+        emit_to: env.output_filesystem.clone(),
+    })
+}
+
+/// The client remote modules are fetched with, below the integrity and policy checks. Replay
+/// and offline builds get one that never touches the network.
+fn fetch_client(
+    config: &EdgeCompileConfig,
+    env: &CompileEnv,
+    policy: &Arc<RemotePolicy>,
+    cwd: &Path,
+    fetch_report: &Arc<FetchReport>,
+) -> Result<Arc<dyn HttpClient>, CompileError> {
+    let cache_dir = http_cache_location(&cache_location(cwd));
+    let recordings = cwd.join(&config.http.recordings);
+    let client: Arc<dyn HttpClient> = if let Some(http_client) = &env.http_client {
+        http_client.clone()
+    } else if config.http.client == HttpClientMode::Replay {
+        Arc::new(FixtureHttpClient::new(recordings.clone(), fetch_report.clone()))
+    } else if config.http.mode == FetchMode::Offline {
+        Arc::new(CachingHttpClient::offline(cache_dir, fetch_report.clone()))
+    } else {
+        let fetcher = ReqwestHttpClient::new(&config.http, policy.clone(), fetch_report.clone())
+            .map_err(|err| CompileError::BadConfig(format!("{:#}", err)))?;
+        Arc::new(CachingHttpClient::new(Arc::new(fetcher), cache_dir, fetch_report.clone()))
+    };
+    Ok(match config.http.client {
+        HttpClientMode::Record if env.http_client.is_none() => Arc::new(RecordingHttpClient::new(client, recordings)),
+        HttpClientMode::Network | HttpClientMode::Replay | HttpClientMode::Record => client,
+    })
+}
+
+impl PreparedCompilation {
+    pub async fn build(&mut self) -> Result<CompileOutput, CompileError> {
+        log::debug!("Compiling with entry: {}", self.entry);
+        self.compiler
+            .build()
+            .await
+            .map_err(|err| CompileError::Internal(format!("build failed: {:?}", err)))?;
+        self.collect().await
+    }
+
+    /// Rebuilds only what is affected by the given absolute paths.
+    pub async fn rebuild(&mut self, changed_files: HashSet<String>, removed_files: HashSet<String>) -> Result<CompileOutput, CompileError> {
+        log::debug!(
+            "Rebuilding entry: {} ({} changed, {} removed)",
+            self.entry,
+            changed_files.len(),
+            removed_files.len()
+        );
+        // Hot updates lead from one hash to the next, so those of earlier builds are of no use
+        // to this one's output and would otherwise pile up over the session.
+        self.output_filesystem.remove_files(|path| is_hot_update(&path.to_string_lossy()));
+        self.compiler
+            .rebuild(changed_files, removed_files)
+            .await
+            .map_err(|err| CompileError::Internal(format!("rebuild failed: {:?}", err)))?;
+        self.collect().await
+    }
+
+    /// Files read by the last build, also available when that build failed.
+    pub fn file_dependencies(&self) -> Vec<PathBuf> {
+        self.compiler
+            .compilation
+            .file_dependencies
+            .iter()
+            .map(|path| path.to_path_buf())
+            .collect()
+    }
+
+    pub fn context_dependencies(&self) -> Vec<PathBuf> {
+        self.compiler
+            .compilation
+            .context_dependencies
+            .iter()
+            .map(|path| path.to_path_buf())
+            .collect()
+    }
+
+    async fn collect(&mut self) -> Result<CompileOutput, CompileError> {
+        let errors: Vec<CompileDiagnostic> = self.compiler
+            .compilation
+            .get_errors()
+            .map(CompileDiagnostic::from_rspack)
+            .collect();
+        let fetch_failures = self.fetch_report.take_failures();
+        let redirects = self.fetch_report.take_redirects();
+        if !errors.is_empty() {
+            return Err(CompileError::from_diagnostics(errors, self.locked).with_fetch_failures(fetch_failures));
+        }
+        let warnings = self.compiler
+            .compilation
+            .get_warnings()
+            .map(CompileDiagnostic::from_rspack)
+            .collect();
+
+        let file_dependencies = self.file_dependencies();
+        let context_dependencies = self.context_dependencies();
+        let remote_modules = remote_modules(&self.compiler);
+
+        // The first build is addressed by the id of its output root, every rebuild gets a
+        // fresh id so previously served bundles stay immutable.
+        self.builds += 1;
+        let id = if self.builds == 1 { self.root_id.clone() } else { next_compilation_id() };
+
+        // The output filesystem keeps assets from earlier builds that were not re-emitted
+        let files: HashMap<String, Vec<u8>> = self.output_filesystem.files.read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter_map(|(path, content)| {
+                let relative = path.strip_prefix(self.output_root.as_std_path()).ok()?;
+                Some((relative.to_string_lossy().to_string(), content.clone()))
+            })
+            .collect();
+        if let Some((filesystem, directory)) = &self.emit_to {
+            for (path, content) in &files {
+                let target = directory.join(path);
+                let written = match target.parent() {
+                    Some(parent) => filesystem.create_dir_all(parent),
+                    None => Ok(()),
+                }
+                .and_then(|_| filesystem.write(&target, content));
+                written.map_err(|err| CompileError::Internal(format!("failed to write {}: {:?}", target, err)))?;
+            }
+        }
+        let hash = self.compiler.compilation.get_hash().map(|hash| hash.to_string());
+        Ok(CompileOutput {
+            id,
+            compiler_id: self.root_id.clone(),
+            hash,
+            entry: self.entry.clone(),
+            entries: self.entries.clone(),
+            files,
+            warnings,
+            file_dependencies,
+            context_dependencies,
+            remote_modules,
+            redirects,
+        })
+    }
+}
+
+// The HMR client as a data URI module, bound to the compiler it takes updates from.
+fn hot_client_entry(compiler_id: &str) -> String {
+    let source = HMR_CLIENT.replace("__COMPILER_ID__", compiler_id);
+    format!("data:text/javascript;base64,{}", BASE64.encode(source))
+}
+
+// Remote modules are identified by their URL, possibly decorated with a module type or layer.
+fn remote_modules(compiler: &Compiler) -> Vec<String> {
+    let mut urls: Vec<String> = compiler
+        .compilation
+        .get_module_graph()
+        .modules()
+        .keys()
+        .filter_map(|identifier| {
+            let identifier = identifier.to_string();
+            let start = identifier.find("https://").or_else(|| identifier.find("http://"))?;
+            let url = identifier[start..].split('|').next().unwrap_or_default();
+            Some(url.to_string())
+        })
+        .collect();
+    urls.sort();
+    urls.dedup();
+    urls
+}
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use rspack_plugin_schemes::HttpResponse;
+
+    use super::*;
+    use crate::http_io::FetchError;
+
+    /// Answers every request with the same module, standing in for the network when seeding.
+    #[derive(Debug)]
+    struct StubHttpClient;
+
+    #[async_trait]
+    impl HttpClient for StubHttpClient {
+        async fn get(&self, _url: &str, _headers: &HashMap<String, String>) -> anyhow::Result<HttpResponse> {
+            Ok(HttpResponse {
+                status: 200,
+                headers: HashMap::from([("cache-control".to_string(), "max-age=0".to_string())]),
+                body: b"export default 1;".to_vec(),
+            })
+        }
+    }
+
+    // A working directory with its own `package.json`, so the HTTP cache lives inside it.
+    fn scratch_context(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rspack-rust-api-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("package.json"), "{}").unwrap();
+        dir
+    }
+
+    // Any attempt to reach the network fails differently: the host never resolves and the
+    // proxy refuses connections.
+    fn unreachable_config(mode: FetchMode, client: HttpClientMode) -> EdgeCompileConfig {
+        let mut config = EdgeCompileConfig::default();
+        config.http.mode = mode;
+        config.http.client = client;
+        config.http.proxy = Some("http://127.0.0.1:9".to_string());
+        config.http.retries = 0;
+        config
+    }
+
+    async fn fetch(config: &EdgeCompileConfig, context: &Path, url: &str) -> anyhow::Result<HttpResponse> {
+        let policy = Arc::new(RemotePolicy::new(&config.remote).unwrap());
+        let report = Arc::new(FetchReport::default());
+        let client = fetch_client(config, &CompileEnv::default(), &policy, context, &report).unwrap();
+        client.get(url, &HashMap::new()).await
+    }
+
+    #[tokio::test]
+    async fn offline_builds_never_touch_the_network() {
+        let context = scratch_context("offline-build");
+        let cache_dir = http_cache_location(&cache_location(&context));
+        CachingHttpClient::new(Arc::new(StubHttpClient), cache_dir, Arc::new(FetchReport::default()))
+            .get("https://example.invalid/a.js", &HashMap::new())
+            .await
+            .unwrap();
+
+        let config = unreachable_config(FetchMode::Offline, HttpClientMode::Network);
+        let response = fetch(&config, &context, "https://example.invalid/a.js").await.unwrap();
+        assert_eq!(response.body, b"export default 1;");
+        let error = fetch(&config, &context, "https://example.invalid/b.js").await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<FetchError>(), Some(FetchError::NotCached { .. })));
+        std::fs::remove_dir_all(context).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replay_builds_never_touch_the_network() {
+        let context = scratch_context("replay-build");
+        let config = unreachable_config(FetchMode::Online, HttpClientMode::Replay);
+        RecordingHttpClient::new(Arc::new(StubHttpClient), context.join(&config.http.recordings))
+            .get("https://example.invalid/a.js", &HashMap::new())
+            .await
+            .unwrap();
+
+        let response = fetch(&config, &context, "https://example.invalid/a.js").await.unwrap();
+        assert_eq!(response.body, b"export default 1;");
+        let error = fetch(&config, &context, "https://example.invalid/b.js").await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<FetchError>(), Some(FetchError::NotRecorded { .. })));
+        std::fs::remove_dir_all(context).unwrap();
+    }
+
+    #[test]
+    fn virtual_paths_stay_inside_the_mounted_tree() {
+        assert_eq!(normalize_virtual_path("src/a.js"), Some(PathBuf::from("src/a.js")));
+        assert_eq!(normalize_virtual_path("./src/./a.js"), Some(PathBuf::from("src/a.js")));
+        for path in ["/etc/passwd", "../a.js", "src/../../a.js", "src/../a.js", "", ".", "./"] {
+            assert_eq!(normalize_virtual_path(path), None, "{:?} should be rejected", path);
+        }
+    }
+
+    #[test]
+    fn submitted_sources_cannot_escape_the_virtual_root() {
+        for path in ["../../etc/cron.d/job", "/etc/cron.d/job"] {
+            let fs = MockFileSystem::new();
+            let sources = VirtualSources {
+                entry: "index.js".to_string(),
+                files: BTreeMap::from([(path.to_string(), b"export default 1;".to_vec())]),
+            };
+            assert!(matches!(sources.mount(&fs), Err(CompileError::BadRequest(_))), "{} should be rejected", path);
+            assert!(fs.files.read().unwrap().is_empty());
+        }
+
+        let fs = MockFileSystem::new();
+        fs.insert(&Path::new(VIRTUAL_ROOT).join("index.js"), Vec::new());
+        assert_eq!(virtual_entry(&fs, "./index.js").unwrap(), format!("{}/index.js", VIRTUAL_ROOT));
+        assert!(virtual_entry(&fs, "../__virtual/index.js").is_err());
+        assert!(virtual_entry(&fs, "/__virtual/index.js").is_err());
+    }
+
+    fn copy_dir(from: &Path, to: &Path) {
+        std::fs::create_dir_all(to).unwrap();
+        for entry in std::fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                copy_dir(&entry.path(), &to.join(entry.file_name()));
+            } else {
+                std::fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+            }
+        }
+    }
 
-    let compiled_files = output_filesystem.files.read().await;
+    #[tokio::test]
+    async fn compiles_the_fixture_from_recordings() {
+        // A copy, so the lockfile and HTTP cache of the build stay out of the repository
+        let context = scratch_context("replay-fixture");
+        copy_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures"), &context.join("fixtures"));
+        let config = unreachable_config(FetchMode::Online, HttpClientMode::Replay);
+        let env = CompileEnv {
+            context: Some(context.clone()),
+            ..CompileEnv::default()
+        };
 
-    compiled_files.iter()
-        .map(|(path, content)| (path.to_string_lossy().to_string(), content.clone()))
-        .collect()
-}
\ No newline at end of file
+        let output = prepare_with(CompileInput::Entry(Some("./fixtures/index.js".to_string())), &config, &env)
+            .await
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(output.remote_modules, vec!["https://esm.sh/react".to_string()]);
+        let main = String::from_utf8(output.files["main.js"].clone()).unwrap();
+        assert!(main.contains("Hello, World 1234!"));
+        assert!(main.contains("Hand-written stub for https://esm.sh/react"));
+        std::fs::remove_dir_all(context).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+use std::convert::Infallible;
+
+use bytes::Bytes;
+use futures::stream::{self, Stream};
+use hyper::body::Frame;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::compile_error::{CompileDiagnostic, CompileError};
+use crate::compiler_pool::RebuildInfo;
+use crate::edge_compile::CompileOutput;
+use crate::response::bundle_url;
+
+/// Something connected clients are told about over `/events`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ServerEvent {
+    /// A watched entry was rebuilt successfully.
+    #[serde(rename_all = "camelCase")]
+    Build {
+        entry: String,
+        compiler_id: String,
+        /// New compilation hash; hot clients still on an older one fetch the update.
+        hash: Option<String>,
+        bundle_id: String,
+        bundle_url: String,
+        rebuild: RebuildInfo,
+    },
+    /// Rebuilding a watched entry failed.
+    #[serde(rename_all = "camelCase")]
+    Error {
+        entry: String,
+        error: &'static str,
+        message: String,
+        diagnostics: Vec<CompileDiagnostic>,
+    },
+}
+
+impl ServerEvent {
+    pub fn build(output: &CompileOutput, rebuild: RebuildInfo) -> Self {
+        ServerEvent::Build {
+            entry: output.entry.clone(),
+            compiler_id: output.compiler_id.clone(),
+            hash: output.hash.clone(),
+            bundle_id: output.id.clone(),
+            bundle_url: bundle_url(&output.id, ""),
+            rebuild,
+        }
+    }
+
+    pub fn error(entry: &str, err: &CompileError) -> Self {
+        ServerEvent::Error {
+            entry: entry.to_string(),
+            error: err.kind(),
+            message: err.to_string(),
+            diagnostics: err.diagnostics().to_vec(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ServerEvent::Build { .. } => "build",
+            ServerEvent::Error { .. } => "error",
+        }
+    }
+
+    /// Encodes the event as a Server-Sent Events message.
+    fn to_sse(&self) -> Bytes {
+        let data = serde_json::to_string(self).expect("server events are always serializable");
+        Bytes::from(format!("event: {}\ndata: {}\n\n", self.name(), data))
+    }
+}
+
+/// Fans server events out to every connected `/events` client.
+pub struct EventHub {
+    sender: broadcast::Sender<ServerEvent>,
+}
+
+impl EventHub {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: ServerEvent) {
+        // Sending only fails when nobody is listening, which is fine
+        let _ = self.sender.send(event);
+    }
+
+    /// A body stream for one SSE client. Clients that fall behind skip the events they missed.
+    pub fn subscribe(&self) -> impl Stream<Item = Result<Frame<Bytes>, Infallible>> + Send + 'static {
+        let receiver = self.sender.subscribe();
+        let hello = stream::once(async { Ok(Frame::data(Bytes::from_static(b": connected\n\n"))) });
+        let events = stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((Ok(Frame::data(event.to_sse())), receiver)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        futures::StreamExt::chain(hello, events)
+    }
+}
@@ -0,0 +1,31 @@
+// Injected into hot compilations: applies updates announced on `/events` for this compiler.
+var compilerId = "__COMPILER_ID__";
+
+if (module.hot && typeof EventSource !== "undefined") {
+  var events = new EventSource("/events");
+  events.addEventListener("build", function (event) {
+    var build = JSON.parse(event.data);
+    if (build.compilerId !== compilerId || build.hash === __webpack_hash__) {
+      return;
+    }
+    if (module.hot.status() !== "idle") {
+      return;
+    }
+    module.hot.check(true).then(
+      function (updated) {
+        if (!updated) {
+          location.reload();
+        }
+      },
+      function (err) {
+        console.warn("[hmr] update failed, reloading", err);
+        location.reload();
+      }
+    );
+  });
+  events.addEventListener("error", function (event) {
+    if (event.data) {
+      console.error("[hmr] rebuild failed", JSON.parse(event.data).message);
+    }
+  });
+}
@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use rspack_plugin_schemes::{HttpClient, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::http_io::{FetchError, FetchReport};
+
+/// Directory below `cache_location` the HTTP cache keeps its entries in.
+pub fn http_cache_location(cache_location: &Path) -> PathBuf {
+    cache_location.join("http")
+}
+
+/// What is known about a stored response besides its body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedResponse {
+    url: String,
+    status: u16,
+    headers: HashMap<String, String>,
+    /// When the response was fetched or last revalidated, in seconds since the epoch.
+    stored_at: u64,
+}
+
+impl CachedResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+
+    fn cache_control(&self) -> CacheControl {
+        CacheControl::parse(self.header("cache-control").unwrap_or_default())
+    }
+
+    /// How long after `stored_at` the response may be used without asking the server.
+    fn freshness_lifetime(&self) -> Option<Duration> {
+        let cache_control = self.cache_control();
+        if cache_control.no_cache {
+            return None;
+        }
+        if cache_control.immutable {
+            return Some(Duration::MAX);
+        }
+        let age = self.header("age").and_then(|age| age.trim().parse::<u64>().ok()).unwrap_or(0);
+        if let Some(max_age) = cache_control.max_age {
+            return Some(Duration::from_secs(max_age.saturating_sub(age)));
+        }
+        // `Expires` only counts without `max-age`, relative to the server's own clock
+        let expires = httpdate::parse_http_date(self.header("expires")?).ok()?;
+        let date = self
+            .header("date")
+            .and_then(|date| httpdate::parse_http_date(date).ok())
+            .unwrap_or_else(|| UNIX_EPOCH + Duration::from_secs(self.stored_at));
+        Some(expires.duration_since(date).unwrap_or_default())
+    }
+
+    fn is_fresh(&self) -> bool {
+        let Some(lifetime) = self.freshness_lifetime() else {
+            return false;
+        };
+        let age = now().saturating_sub(self.stored_at);
+        Duration::from_secs(age) < lifetime
+    }
+
+    /// Whether there is anything that makes storing the response worthwhile.
+    fn is_storable(&self) -> bool {
+        let cache_control = self.cache_control();
+        self.status == 200
+            && !cache_control.no_store
+            && (cache_control.immutable
+                || cache_control.max_age.is_some()
+                || self.header("expires").is_some()
+                || self.header("etag").is_some()
+                || self.header("last-modified").is_some())
+    }
+}
+
+#[derive(Debug, Default)]
+struct CacheControl {
+    max_age: Option<u64>,
+    immutable: bool,
+    no_cache: bool,
+    no_store: bool,
+}
+
+impl CacheControl {
+    fn parse(value: &str) -> Self {
+        let mut cache_control = Self::default();
+        for directive in value.split(',') {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            match name.to_ascii_lowercase().as_str() {
+                "max-age" => cache_control.max_age = argument.and_then(|seconds| seconds.parse().ok()),
+                "immutable" => cache_control.immutable = true,
+                "no-cache" => cache_control.no_cache = true,
+                "no-store" => cache_control.no_store = true,
+                _ => {}
+            }
+        }
+        cache_control
+    }
+}
+
+fn header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Caches remote modules on disk following HTTP caching rules.
+///
+/// Fresh responses (`max-age`, `immutable`, `Expires`) are served without touching the
+/// network. Stale ones are revalidated with `If-None-Match`/`If-Modified-Since`, and a `304`
+/// answers from the stored body. Only `200` responses that are not `no-store` are kept.
+///
+/// Without an inner client the cache is offline: every stored response is served no matter
+/// how stale, and anything else fails with [`FetchError::NotCached`].
+pub struct CachingHttpClient {
+    inner: Option<Arc<dyn HttpClient>>,
+    directory: PathBuf,
+    report: Arc<FetchReport>,
+}
+
+impl CachingHttpClient {
+    pub fn new(inner: Arc<dyn HttpClient>, directory: PathBuf, report: Arc<FetchReport>) -> Self {
+        Self {
+            inner: Some(inner),
+            directory,
+            report,
+        }
+    }
+
+    pub fn offline(directory: PathBuf, report: Arc<FetchReport>) -> Self {
+        Self {
+            inner: None,
+            directory,
+            report,
+        }
+    }
+
+    async fn store(&self, meta: &CachedResponse, body: Option<&[u8]>) -> std::io::Result<()> {
+        let (meta_path, body_path) = entry_paths(&self.directory, &meta.url);
+        tokio::fs::create_dir_all(&self.directory).await?;
+        if let Some(body) = body {
+            write_atomically(&body_path, body).await?;
+        }
+        let meta = serde_json::to_vec(meta).expect("cached responses are always serializable");
+        write_atomically(&meta_path, &meta).await
+    }
+}
+
+fn entry_paths(directory: &Path, url: &str) -> (PathBuf, PathBuf) {
+    let key = format!("{:x}", Sha256::digest(url));
+    (directory.join(format!("{}.json", key)), directory.join(format!("{}.body", key)))
+}
+
+async fn load(directory: &Path, url: &str) -> Option<(CachedResponse, Vec<u8>)> {
+    let (meta_path, body_path) = entry_paths(directory, url);
+    let meta: CachedResponse = serde_json::from_slice(&tokio::fs::read(meta_path).await.ok()?).ok()?;
+    // Two URLs with the same digest are not worth handling beyond not mixing them up
+    if meta.url != url {
+        return None;
+    }
+    let body = tokio::fs::read(body_path).await.ok()?;
+    Some((meta, body))
+}
+
+/// The stored body for `url`, fresh or not.
+pub async fn cached_body(directory: &Path, url: &str) -> Option<Vec<u8>> {
+    load(directory, url).await.map(|(_, body)| body)
+}
+
+/// Forgets `url`, so the next fetch goes to the network. Returns whether it was cached.
+pub async fn evict(directory: &Path, url: &str) -> bool {
+    let (meta_path, body_path) = entry_paths(directory, url);
+    let removed = tokio::fs::remove_file(meta_path).await.is_ok();
+    let _ = tokio::fs::remove_file(body_path).await;
+    removed
+}
+
+// Concurrent compilations may fetch the same URL, readers must never see half a file
+async fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let tmp = temp_path(path);
+    let written = match tokio::fs::write(&tmp, content).await {
+        Ok(()) => tokio::fs::rename(&tmp, path).await,
+        Err(err) => Err(err),
+    };
+    if written.is_err() {
+        let _ = tokio::fs::remove_file(&tmp).await;
+    }
+    written
+}
+
+/// A path next to `path` to write its new content to before renaming it into place. Every
+/// call returns a different one, so concurrent writers never share a temporary file.
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".tmp-{}-{}", std::process::id(), NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+    PathBuf::from(name)
+}
+
+impl std::fmt::Debug for CachingHttpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachingHttpClient")
+            .field("directory", &self.directory)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl HttpClient for CachingHttpClient {
+    async fn get(&self, url: &str, headers: &HashMap<String, String>) -> Result<HttpResponse> {
+        let cached = load(&self.directory, url).await;
+        if let Some((meta, body)) = &cached {
+            if meta.is_fresh() || self.inner.is_none() {
+                return Ok(HttpResponse {
+                    status: meta.status,
+                    headers: meta.headers.clone(),
+                    body: body.clone(),
+                });
+            }
+        }
+
+        let mut request_headers = headers.clone();
+        if let Some((meta, _)) = &cached {
+            if let Some(etag) = meta.header("etag") {
+                request_headers.insert("If-None-Match".to_string(), etag.to_string());
+            }
+            if let Some(last_modified) = meta.header("last-modified") {
+                request_headers.insert("If-Modified-Since".to_string(), last_modified.to_string());
+            }
+        }
+        let Some(inner) = &self.inner else {
+            let error = FetchError::NotCached { url: url.to_string() };
+            self.report.record_failure(error.clone());
+            return Err(error.into());
+        };
+        let response = inner.get(url, &request_headers).await?;
+
+        if let (304, Some((mut meta, body))) = (response.status, cached) {
+            // A 304 refreshes the validators and freshness of what is stored
+            for name in ["cache-control", "etag", "expires", "last-modified", "date", "age"] {
+                if let Some(value) = header(&response.headers, name) {
+                    meta.headers.retain(|key, _| !key.eq_ignore_ascii_case(name));
+                    meta.headers.insert(name.to_string(), value.to_string());
+                }
+            }
+            meta.stored_at = now();
+            if let Err(err) = self.store(&meta, None).await {
+                log::warn!("Error updating HTTP cache for {}: {:?}", url, err);
+            }
+            return Ok(HttpResponse {
+                status: meta.status,
+                headers: meta.headers,
+                body,
+            });
+        }
+
+        let meta = CachedResponse {
+            url: url.to_string(),
+            status: response.status,
+            headers: response.headers.clone(),
+            stored_at: now(),
+        };
+        if meta.is_storable() {
+            if let Err(err) = self.store(&meta, Some(&response.body)).await {
+                log::warn!("Error writing HTTP cache for {}: {:?}", url, err);
+            }
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rspack-rust-api-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    async fn seed(directory: &Path, url: &str, body: &str) {
+        let client = CachingHttpClient::offline(directory.to_path_buf(), Arc::new(FetchReport::default()));
+        let meta = CachedResponse {
+            url: url.to_string(),
+            status: 200,
+            headers: HashMap::from([("cache-control".to_string(), "max-age=60".to_string())]),
+            // Long stale, offline serves it anyway
+            stored_at: 0,
+        };
+        client.store(&meta, Some(body.as_bytes())).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn offline_serves_cached_entries() {
+        let directory = scratch_dir("offline-cached");
+        seed(&directory, "https://example.invalid/a.js", "export default 1;").await;
+
+        let client = CachingHttpClient::offline(directory.clone(), Arc::new(FetchReport::default()));
+        let response = client.get("https://example.invalid/a.js", &HashMap::new()).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"export default 1;");
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn offline_fails_uncached_urls() {
+        let directory = scratch_dir("offline-uncached");
+        seed(&directory, "https://example.invalid/a.js", "export default 1;").await;
+
+        let report = Arc::new(FetchReport::default());
+        let client = CachingHttpClient::offline(directory.clone(), report.clone());
+        let error = client.get("https://example.invalid/b.js", &HashMap::new()).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<FetchError>(),
+            Some(FetchError::NotCached { url }) if url == "https://example.invalid/b.js"
+        ));
+        assert_eq!(report.take_failures().len(), 1);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn temp_paths_are_unique_per_write() {
+        let meta = Path::new("/cache/key.json");
+        let body = Path::new("/cache/key.body");
+        assert_ne!(temp_path(meta), temp_path(meta));
+        assert!(temp_path(meta).to_string_lossy().starts_with("/cache/key.json.tmp-"));
+        assert!(temp_path(body).to_string_lossy().starts_with("/cache/key.body.tmp-"));
+    }
+
+    #[tokio::test]
+    async fn concurrent_stores_leave_one_complete_entry() {
+        let directory = scratch_dir("concurrent-store");
+        let url = "https://example.invalid/a.js";
+        let bodies: Vec<String> = (0..16).map(|n| format!("export default {};", n)).collect();
+        futures::future::join_all(bodies.iter().map(|body| seed(&directory, url, body))).await;
+
+        let (meta, body) = load(&directory, url).await.unwrap();
+        assert_eq!(meta.url, url);
+        assert!(bodies.iter().any(|stored| stored.as_bytes() == body));
+        // Only the entry itself is left, no temporary files
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 2);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
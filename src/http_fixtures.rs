@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context as _, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rspack_plugin_schemes::{HttpClient, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::http_io::{FetchError, FetchReport};
+use crate::response::AssetEncoding;
+
+/// A recorded response, one JSON file per URL so recordings can be reviewed and edited.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Recording {
+    url: String,
+    status: u16,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    /// `utf8` bodies are stored as text, anything else as base64.
+    encoding: AssetEncoding,
+    body: String,
+}
+
+impl Recording {
+    fn new(url: &str, response: &HttpResponse) -> Self {
+        let (encoding, body) = match std::str::from_utf8(&response.body) {
+            Ok(text) => (AssetEncoding::Utf8, text.to_string()),
+            Err(_) => (AssetEncoding::Base64, BASE64.encode(&response.body)),
+        };
+        Self {
+            url: url.to_string(),
+            status: response.status,
+            headers: response.headers.clone(),
+            encoding,
+            body,
+        }
+    }
+
+    fn into_response(self) -> Result<HttpResponse> {
+        let body = match self.encoding {
+            AssetEncoding::Utf8 => self.body.into_bytes(),
+            AssetEncoding::Base64 => BASE64
+                .decode(&self.body)
+                .with_context(|| format!("recording of {} has an invalid base64 body", self.url))?,
+        };
+        Ok(HttpResponse {
+            status: self.status,
+            headers: self.headers,
+            body,
+        })
+    }
+}
+
+/// File a recording of `url` lives in: the URL made filesystem-safe, plus a short digest so
+/// URLs that sanitize to the same name do not collide.
+fn recording_path(directory: &Path, url: &str) -> PathBuf {
+    let readable: String = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .take(100)
+        .collect();
+    let digest = format!("{:x}", Sha256::digest(url));
+    directory.join(format!("{}-{}.json", readable, &digest[..8]))
+}
+
+/// Serves remote modules from recordings only, for builds that must not touch the network.
+/// A URL without a recording fails with [`FetchError::NotRecorded`].
+pub struct FixtureHttpClient {
+    directory: PathBuf,
+    report: Arc<FetchReport>,
+}
+
+impl FixtureHttpClient {
+    pub fn new(directory: PathBuf, report: Arc<FetchReport>) -> Self {
+        Self { directory, report }
+    }
+}
+
+impl std::fmt::Debug for FixtureHttpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FixtureHttpClient")
+            .field("directory", &self.directory)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl HttpClient for FixtureHttpClient {
+    async fn get(&self, url: &str, _headers: &HashMap<String, String>) -> Result<HttpResponse> {
+        let path = recording_path(&self.directory, url);
+        let content = match tokio::fs::read(&path).await {
+            Ok(content) => content,
+            Err(_) => {
+                let error = FetchError::NotRecorded {
+                    url: url.to_string(),
+                    path: path.to_string_lossy().to_string(),
+                };
+                self.report.record_failure(error.clone());
+                return Err(error.into());
+            }
+        };
+        let recording: Recording = serde_json::from_slice(&content)
+            .with_context(|| format!("invalid recording {}", path.display()))?;
+        recording.into_response()
+    }
+}
+
+/// Fetches through another client and writes every response into the recordings directory,
+/// ready to be replayed by [`FixtureHttpClient`].
+pub struct RecordingHttpClient {
+    inner: Arc<dyn HttpClient>,
+    directory: PathBuf,
+}
+
+impl RecordingHttpClient {
+    pub fn new(inner: Arc<dyn HttpClient>, directory: PathBuf) -> Self {
+        Self { inner, directory }
+    }
+}
+
+impl std::fmt::Debug for RecordingHttpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordingHttpClient")
+            .field("directory", &self.directory)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl HttpClient for RecordingHttpClient {
+    async fn get(&self, url: &str, headers: &HashMap<String, String>) -> Result<HttpResponse> {
+        let response = self.inner.get(url, headers).await?;
+        let recording = serde_json::to_vec_pretty(&Recording::new(url, &response))
+            .expect("recordings are always serializable");
+        let path = recording_path(&self.directory, url);
+        let written = async {
+            tokio::fs::create_dir_all(&self.directory).await?;
+            tokio::fs::write(&path, recording).await
+        };
+        if let Err(err) = written.await {
+            log::warn!("Error recording {} to {}: {:?}", url, path.display(), err);
+        }
+        Ok(response)
+    }
+}
@@ -1,43 +1,657 @@
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use async_trait::async_trait;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use reqwest::header::{HeaderMap, HeaderValue, SET_COOKIE};
+use reqwest::{Client, Method, NoProxy, Proxy};
+use serde::Serialize;
 use std::collections::HashMap;
-use std::sync::Arc;
-use rspack_plugin_schemes::{HttpClient, HttpRequest, HttpResponse};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use rspack_plugin_schemes::{HttpClient, HttpResponse};
+use url::Url;
 
+use crate::config::HttpConfig;
+use crate::remote_policy::{PublicResolver, RemotePolicy, DENIED_MESSAGE};
 
-#[derive(Debug)]
+/// Why fetching a remote module failed, after any retries.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum FetchError {
+  #[serde(rename_all = "camelCase")]
+  Timeout { url: String, attempts: u32 },
+  #[serde(rename_all = "camelCase")]
+  Connect { url: String, attempts: u32, message: String },
+  #[serde(rename_all = "camelCase")]
+  Network { url: String, attempts: u32, message: String },
+  /// The server kept answering with a 5xx or 429.
+  #[serde(rename_all = "camelCase")]
+  Status { url: String, attempts: u32, status: u16 },
+  #[serde(rename_all = "camelCase")]
+  TooLarge { url: String, limit: usize },
+  /// Offline builds only use what is already in the HTTP cache.
+  #[serde(rename_all = "camelCase")]
+  NotCached { url: String },
+  /// Replayed builds only use recorded responses.
+  #[serde(rename_all = "camelCase")]
+  NotRecorded { url: String, path: String },
+  /// The body does not match the integrity expected for it.
+  #[serde(rename_all = "camelCase")]
+  Integrity { url: String, expected: String, actual: String },
+  /// Following the redirects of `url` failed; `chain` lists the URLs visited after it.
+  #[serde(rename_all = "camelCase")]
+  Redirect { url: String, chain: Vec<String>, message: String },
+}
+
+impl FetchError {
+  fn from_reqwest(url: &str, attempts: u32, err: &reqwest::Error) -> Self {
+    let url = url.to_string();
+    if err.is_timeout() {
+      FetchError::Timeout { url, attempts }
+    } else if err.is_connect() {
+      FetchError::Connect { url, attempts, message: error_chain(err) }
+    } else {
+      FetchError::Network { url, attempts, message: error_chain(err) }
+    }
+  }
+
+  fn is_retryable(&self) -> bool {
+    // The resolver refusing a private address will refuse it again
+    if let FetchError::Connect { message, .. } = self {
+      if message.contains(DENIED_MESSAGE) {
+        return false;
+      }
+    }
+    !matches!(self, FetchError::TooLarge { .. }
+        | FetchError::NotCached { .. }
+        | FetchError::NotRecorded { .. }
+        | FetchError::Integrity { .. }
+        | FetchError::Redirect { .. })
+  }
+
+  fn with_attempts(self, total: u32) -> Self {
+    match self {
+      FetchError::Timeout { url, .. } => FetchError::Timeout { url, attempts: total },
+      FetchError::Connect { url, message, .. } => FetchError::Connect { url, attempts: total, message },
+      FetchError::Network { url, message, .. } => FetchError::Network { url, attempts: total, message },
+      FetchError::Status { url, status, .. } => FetchError::Status { url, attempts: total, status },
+      FetchError::TooLarge { .. }
+      | FetchError::NotCached { .. }
+      | FetchError::NotRecorded { .. }
+      | FetchError::Integrity { .. }
+      | FetchError::Redirect { .. } => self,
+    }
+  }
+
+  pub fn is_timeout(&self) -> bool {
+    matches!(self, FetchError::Timeout { .. })
+  }
+
+  pub fn is_integrity(&self) -> bool {
+    matches!(self, FetchError::Integrity { .. })
+  }
+}
+
+impl fmt::Display for FetchError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      FetchError::Timeout { url, attempts } => write!(f, "fetch {} timed out ({} attempts)", url, attempts),
+      FetchError::Connect { url, attempts, message } => {
+        write!(f, "fetch {} failed to connect ({} attempts): {}", url, attempts, message)
+      }
+      FetchError::Network { url, attempts, message } => {
+        write!(f, "fetch {} failed ({} attempts): {}", url, attempts, message)
+      }
+      FetchError::Status { url, attempts, status } => {
+        write!(f, "fetch {} answered {} ({} attempts)", url, status, attempts)
+      }
+      FetchError::TooLarge { url, limit } => write!(f, "fetch {} exceeds {} bytes", url, limit),
+      FetchError::NotCached { url } => write!(f, "fetch {} is not possible offline and it is not cached", url),
+      FetchError::NotRecorded { url, path } => write!(f, "fetch {} has no recording at {}", url, path),
+      FetchError::Integrity { url, expected, actual } => {
+        write!(f, "fetch {} failed integrity check: expected {}, got {}", url, expected, actual)
+      }
+      FetchError::Redirect { url, chain, message } => {
+        write!(f, "fetch {} failed following redirects", url)?;
+        for hop in chain {
+          write!(f, " -> {}", hop)?;
+        }
+        write!(f, ": {}", message)
+      }
+    }
+  }
+}
+
+impl std::error::Error for FetchError {}
+
+/// One redirect that was followed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedirectHop {
+  pub status: u16,
+  /// Absolute URL the `Location` header pointed to.
+  pub location: String,
+}
+
+/// The redirects fetching `url` went through, in order.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedirectChain {
+  pub url: String,
+  pub hops: Vec<RedirectHop>,
+}
+
+impl RedirectChain {
+  /// The URL the content was finally fetched from.
+  pub fn resolved(&self) -> &str {
+    self.hops.last().map_or(&self.url, |hop| &hop.location)
+  }
+}
+
+/// Collects what happened to the fetches of one compilation, so the compile result can
+/// report failed fetches and followed redirects.
+#[derive(Debug, Default)]
+pub struct FetchReport {
+  failures: Mutex<Vec<FetchError>>,
+  redirects: Mutex<Vec<RedirectChain>>,
+}
+
+impl FetchReport {
+  pub fn record_failure(&self, error: FetchError) {
+    self.failures.lock().unwrap().push(error);
+  }
+
+  pub fn record_redirect(&self, chain: RedirectChain) {
+    self.redirects.lock().unwrap().push(chain);
+  }
+
+  /// Returns and forgets the failures recorded so far.
+  pub fn take_failures(&self) -> Vec<FetchError> {
+    std::mem::take(&mut *self.failures.lock().unwrap())
+  }
+
+  /// Returns and forgets the redirect chains recorded so far.
+  pub fn take_redirects(&self) -> Vec<RedirectChain> {
+    std::mem::take(&mut *self.redirects.lock().unwrap())
+  }
+}
+
+// reqwest keeps the interesting part (DNS, TLS, ...) in the source chain
+fn error_chain(err: &dyn std::error::Error) -> String {
+  let mut message = err.to_string();
+  let mut source = err.source();
+  while let Some(err) = source {
+    message.push_str(": ");
+    message.push_str(&err.to_string());
+    source = err.source();
+  }
+  message
+}
+
+/// Fetches over the network, retrying transient failures and following redirects itself.
+///
+/// Every redirect target is checked against the remote policy before it is requested, and a
+/// chain longer than `max_redirects` fails. `HttpUriPlugin` gets a single `302` to the final
+/// URL for a redirected `get`, so it locks the resolved URL and resolves relative imports
+/// against it; the final response is kept until the plugin asks for that URL.
 pub struct ReqwestHttpClient {
   client: Client,
+  policy: Arc<RemotePolicy>,
+  max_redirects: usize,
+  retries: u32,
+  retry_backoff: Duration,
+  max_retry_delay: Duration,
+  max_response_size: usize,
+  report: Arc<FetchReport>,
+  /// Final responses of redirect chains by URL, served once.
+  pending: Mutex<HashMap<String, HttpResponse>>,
 }
 
 impl ReqwestHttpClient {
-  pub fn new() -> Self {
-    Self {
-      client: Client::new(),
+  /// Builds a client that routes through the configured proxy (or the one from the
+  /// environment), checks redirect targets against `policy` and, unless the policy allows
+  /// private networks, only connects to public addresses.
+  pub fn new(config: &HttpConfig, policy: Arc<RemotePolicy>, report: Arc<FetchReport>) -> Result<Self> {
+    // System proxies are replaced by our own list so the config can take precedence over them
+    let mut builder = Client::builder()
+      .no_proxy()
+      .redirect(reqwest::redirect::Policy::none())
+      .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+      .timeout(Duration::from_millis(config.timeout_ms));
+    let mut proxy_hosts = Vec::new();
+    for (url, proxy) in proxies(config)? {
+      // Proxy URLs from the environment may leave out the scheme
+      let parsed = Url::parse(&url).or_else(|_| Url::parse(&format!("http://{}", url)));
+      proxy_hosts.extend(parsed.ok().and_then(|url| url.host_str().map(str::to_string)));
+      builder = builder.proxy(proxy);
     }
+    if !policy.allows_private_networks() {
+      builder = builder.dns_resolver(Arc::new(PublicResolver::new(proxy_hosts)));
+    }
+    Ok(Self {
+      client: builder.build()?,
+      policy,
+      max_redirects: config.max_redirects,
+      retries: config.retries,
+      retry_backoff: Duration::from_millis(config.retry_backoff_ms),
+      max_retry_delay: Duration::from_millis(config.max_retry_delay_ms),
+      max_response_size: config.max_response_size,
+      report,
+      pending: Mutex::new(HashMap::new()),
+    })
   }
-}
 
-#[async_trait]
-impl HttpClient for ReqwestHttpClient {
-  async fn get(&self, url: &str, headers: &HashMap<String, String>) -> Result<HttpResponse> {
-    let mut req = self.client.get(url);
+  /// Sends `method` to `url`, following redirects, and returns the final response.
+  pub async fn request(
+    &self,
+    method: Method,
+    url: &str,
+    headers: &HashMap<String, String>,
+    body: Option<Vec<u8>>,
+  ) -> Result<HttpResponse> {
+    let (response, _) = self.follow(method, url, headers, body).await?;
+    Ok(response)
+  }
+
+  /// Like `get` without the body, e.g. to check whether a module changed.
+  pub async fn head(&self, url: &str, headers: &HashMap<String, String>) -> Result<HttpResponse> {
+    self.request(Method::HEAD, url, headers, None).await
+  }
+
+  async fn follow(
+    &self,
+    mut method: Method,
+    url: &str,
+    headers: &HashMap<String, String>,
+    mut body: Option<Vec<u8>>,
+  ) -> Result<(HttpResponse, RedirectChain)> {
+    let mut chain = RedirectChain { url: url.to_string(), hops: Vec::new() };
+    loop {
+      let current = chain.resolved().to_string();
+      let response = match self.fetch_with_retries(&method, &current, headers, body.as_deref()).await {
+        Ok(response) => response,
+        Err(error) if chain.hops.is_empty() => return Err(self.fail(error)),
+        Err(error) => return Err(self.fail(redirect_error(&chain, error.to_string()))),
+      };
+      if !is_redirect(response.status) {
+        if !chain.hops.is_empty() {
+          self.report.record_redirect(chain.clone());
+        }
+        return Ok((response, chain));
+      }
+
+      let Some(location) = header(&response.headers, "location") else {
+        return Err(self.fail(redirect_error(&chain, format!("{} without a Location header", response.status))));
+      };
+      let next = match Url::parse(&current).and_then(|base| base.join(location)) {
+        Ok(next) => next.to_string(),
+        Err(err) => return Err(self.fail(redirect_error(&chain, format!("invalid Location `{}`: {}", location, err)))),
+      };
+      if chain.hops.len() >= self.max_redirects {
+        return Err(self.fail(redirect_error(&chain, format!("more than {} redirects", self.max_redirects))));
+      }
+      if let Err(reason) = self.policy.check(&next) {
+        anyhow::bail!("redirect of {} to {} {}: {}", url, next, DENIED_MESSAGE, reason);
+      }
+      // Like browsers: 303 always turns into a GET, 301 and 302 only for POST
+      let to_get = match response.status {
+        303 => method != Method::HEAD,
+        301 | 302 => method == Method::POST,
+        _ => false,
+      };
+      if to_get {
+        method = Method::GET;
+        body = None;
+      }
+      chain.hops.push(RedirectHop { status: response.status, location: next });
+    }
+  }
+
+  fn fail(&self, error: FetchError) -> anyhow::Error {
+    self.report.record_failure(error.clone());
+    error.into()
+  }
+
+  async fn fetch_with_retries(
+    &self,
+    method: &Method,
+    url: &str,
+    headers: &HashMap<String, String>,
+    body: Option<&[u8]>,
+  ) -> Result<HttpResponse, FetchError> {
+    let mut retry = 0;
+    loop {
+      let (error, delay) = match self.fetch_once(method, url, headers, body).await {
+        Ok(response) if is_retryable_status(response.status) => {
+          let delay = retry_after(&response.headers).unwrap_or_else(|| self.backoff(retry));
+          let error = FetchError::Status { url: url.to_string(), attempts: 1, status: response.status };
+          (error, delay)
+        }
+        Ok(response) => return Ok(response),
+        Err(error) => (error, self.backoff(retry)),
+      };
+      if !error.is_retryable() || retry >= self.retries {
+        return Err(error.with_attempts(retry + 1));
+      }
+      retry += 1;
+      tokio::time::sleep(delay.min(self.max_retry_delay)).await;
+    }
+  }
+
+  async fn fetch_once(
+    &self,
+    method: &Method,
+    url: &str,
+    headers: &HashMap<String, String>,
+    body: Option<&[u8]>,
+  ) -> Result<HttpResponse, FetchError> {
+    let mut req = self.client.request(method.clone(), url);
 
     for (key, value) in headers.iter() {
       req = req.header(key, value);
     }
+    if let Some(body) = body {
+      req = req.body(body.to_vec());
+    }
 
-    let response = req.send().await?;
+    let mut response = req.send().await.map_err(|err| FetchError::from_reqwest(url, 1, &err))?;
+    let too_large = || FetchError::TooLarge { url: url.to_string(), limit: self.max_response_size };
+    // A HEAD response announces the length of a body it does not have
+    if *method != Method::HEAD && response.content_length().is_some_and(|length| length > self.max_response_size as u64) {
+      return Err(too_large());
+    }
     let status = response.status().as_u16();
-    let headers = response
-      .headers()
-      .iter()
-      .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
-      .collect();
-    let body = response.bytes().await?.to_vec();
+    let headers = collect_headers(response.headers());
+    // The length header may be missing or wrong, so the limit is enforced while reading
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|err| FetchError::from_reqwest(url, 1, &err))? {
+      if body.len() + chunk.len() > self.max_response_size {
+        return Err(too_large());
+      }
+      body.extend_from_slice(&chunk);
+    }
 
     Ok(HttpResponse { status, headers, body })
   }
+
+  fn backoff(&self, retry: u32) -> Duration {
+    self
+      .retry_backoff
+      .saturating_mul(2u32.saturating_pow(retry))
+      .min(self.max_retry_delay)
+  }
+}
+
+impl fmt::Debug for ReqwestHttpClient {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ReqwestHttpClient")
+      .field("max_redirects", &self.max_redirects)
+      .field("retries", &self.retries)
+      .field("max_response_size", &self.max_response_size)
+      .finish_non_exhaustive()
+  }
+}
+
+fn redirect_error(chain: &RedirectChain, message: String) -> FetchError {
+  FetchError::Redirect {
+    url: chain.url.clone(),
+    chain: chain.hops.iter().map(|hop| hop.location.clone()).collect(),
+    message,
+  }
+}
+
+fn is_redirect(status: u16) -> bool {
+  matches!(status, 301 | 302 | 303 | 307 | 308)
+}
+
+// `HttpResponse` holds one value per header name. Repeated headers are joined with commas as
+// HTTP allows for lists, so a joined value cannot be split back when one of the values has a
+// comma of its own. `Set-Cookie` is never folded that way; its values are joined with newlines
+// instead, which no header value can contain, so `lines()` recovers them. Values that are not
+// visible ASCII are decoded as latin-1, which maps every byte to one char so nothing is lost.
+fn collect_headers(headers: &HeaderMap) -> HashMap<String, String> {
+  let mut collected: HashMap<String, String> = HashMap::new();
+  for (name, value) in headers {
+    let value = header_value(value);
+    let separator = if name == SET_COOKIE { "\n" } else { ", " };
+    collected
+      .entry(name.to_string())
+      .and_modify(|joined| {
+        joined.push_str(separator);
+        joined.push_str(&value);
+      })
+      .or_insert(value);
+  }
+  collected
+}
+
+fn header_value(value: &HeaderValue) -> String {
+  match value.to_str() {
+    Ok(value) => value.to_string(),
+    Err(_) => value.as_bytes().iter().map(|&byte| char::from(byte)).collect(),
+  }
+}
+
+fn header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+  headers
+    .iter()
+    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+    .map(|(_, value)| value.as_str())
+}
+
+fn is_retryable_status(status: u16) -> bool {
+  status == 429 || ((500..600).contains(&status) && status != 501)
+}
+
+// `Retry-After` is either a number of seconds or an HTTP date
+fn retry_after(headers: &HashMap<String, String>) -> Option<Duration> {
+  let value = header(headers, "retry-after")?.trim();
+  if let Ok(seconds) = value.parse::<u64>() {
+    return Some(Duration::from_secs(seconds));
+  }
+  let date = httpdate::parse_http_date(value).ok()?;
+  Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+// Each proxy with the URL it was built from.
+fn proxies(config: &HttpConfig) -> Result<Vec<(String, Proxy)>> {
+  let no_proxy = match &config.no_proxy {
+    Some(no_proxy) => NoProxy::from_string(no_proxy),
+    None => NoProxy::from_env(),
+  };
+  let proxies = match &config.proxy {
+    Some(url) => vec![(url.clone(), Proxy::all(url).with_context(|| format!("invalid proxy `{}`", url))?)],
+    None => {
+      let mut proxies = Vec::new();
+      if let Some(url) = env_proxy("HTTPS_PROXY") {
+        let proxy = Proxy::https(&url).with_context(|| format!("invalid HTTPS_PROXY `{}`", url))?;
+        proxies.push((url, proxy));
+      }
+      if let Some(url) = env_proxy("HTTP_PROXY") {
+        let proxy = Proxy::http(&url).with_context(|| format!("invalid HTTP_PROXY `{}`", url))?;
+        proxies.push((url, proxy));
+      }
+      proxies
+    }
+  };
+  Ok(
+    proxies
+      .into_iter()
+      .map(|(url, proxy)| {
+        let proxy = proxy.no_proxy(no_proxy.clone());
+        let proxy = match &config.proxy_username {
+          Some(username) => proxy.basic_auth(username, config.proxy_password.as_deref().unwrap_or_default()),
+          None => proxy,
+        };
+        (url, proxy)
+      })
+      .collect(),
+  )
+}
+
+fn env_proxy(name: &str) -> Option<String> {
+  std::env::var(name)
+    .or_else(|_| std::env::var(name.to_lowercase()))
+    .ok()
+    .filter(|url| !url.is_empty())
+}
+
+#[async_trait]
+impl HttpClient for ReqwestHttpClient {
+  async fn get(&self, url: &str, headers: &HashMap<String, String>) -> Result<HttpResponse> {
+    if let Some(response) = self.pending.lock().unwrap().remove(url) {
+      return Ok(response);
+    }
+    let (response, chain) = self.follow(Method::GET, url, headers, None).await?;
+    if chain.hops.is_empty() {
+      return Ok(response);
+    }
+    let resolved = chain.resolved().to_string();
+    self.pending.lock().unwrap().insert(resolved.clone(), response);
+    Ok(HttpResponse {
+      status: 302,
+      headers: HashMap::from([("location".to_string(), resolved)]),
+      body: Vec::new(),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::io::{AsyncReadExt, AsyncWriteExt};
+  use tokio::net::{TcpListener, TcpStream};
+
+  use super::*;
+  use crate::config::RemoteConfig;
+
+  // Answers every connection with `respond(method, path, body)` and closes it. Returns the
+  // base URL of the server.
+  async fn serve(respond: fn(&str, &str, &str) -> String) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      while let Ok((mut stream, _)) = listener.accept().await {
+        tokio::spawn(async move {
+          let (head, body) = read_request(&mut stream).await;
+          let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+          let (method, path) = (request_line.next().unwrap_or_default(), request_line.next().unwrap_or_default());
+          let _ = stream.write_all(respond(method, path, &body).as_bytes()).await;
+        });
+      }
+    });
+    format!("http://{}", address)
+  }
+
+  async fn read_request(stream: &mut TcpStream) -> (String, String) {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    let head_end = loop {
+      if let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+        break end + 4;
+      }
+      let read = stream.read(&mut buffer).await.unwrap();
+      if read == 0 {
+        break request.len();
+      }
+      request.extend_from_slice(&buffer[..read]);
+    };
+    let head = String::from_utf8_lossy(&request[..head_end]).to_string();
+    let length: usize = head
+      .lines()
+      .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|value| value.trim().to_string()))
+      .and_then(|value| value.parse().ok())
+      .unwrap_or(0);
+    while request.len() < head_end + length {
+      let read = stream.read(&mut buffer).await.unwrap();
+      if read == 0 {
+        break;
+      }
+      request.extend_from_slice(&buffer[..read]);
+    }
+    (head, String::from_utf8_lossy(&request[head_end..]).to_string())
+  }
+
+  fn response(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+    let mut response = format!("HTTP/1.1 {}\r\nconnection: close\r\n", status);
+    for (name, value) in headers {
+      response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if !headers.iter().any(|(name, _)| *name == "content-length") {
+      response.push_str(&format!("content-length: {}\r\n", body.len()));
+    }
+    response.push_str("\r\n");
+    response.push_str(body);
+    response
+  }
+
+  // Echoes the method, path and body of the request, and sees `/old` over to `/new`.
+  fn echo(method: &str, path: &str, body: &str) -> String {
+    match path {
+      "/old" => response("303 See Other", &[("location", "/new")], ""),
+      "/large" => response("200 OK", &[("content-length", "1000000")], ""),
+      _ => response("200 OK", &[], &format!("{} {} {}", method, path, body)),
+    }
+  }
+
+  fn client() -> ReqwestHttpClient {
+    let config = HttpConfig {
+      no_proxy: Some("127.0.0.1".to_string()),
+      max_response_size: 100,
+      ..HttpConfig::default()
+    };
+    let remote = RemoteConfig {
+      allow_private_networks: true,
+      ..RemoteConfig::default()
+    };
+    let policy = Arc::new(RemotePolicy::new(&remote).unwrap());
+    ReqwestHttpClient::new(&config, policy, Arc::new(FetchReport::default())).unwrap()
+  }
+
+  #[tokio::test]
+  async fn requests_carry_their_method_and_body() {
+    let base = serve(echo).await;
+    let response = client()
+      .request(Method::PUT, &format!("{}/module", base), &HashMap::new(), Some(b"payload".to_vec()))
+      .await
+      .unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, b"PUT /module payload");
+  }
+
+  #[tokio::test]
+  async fn see_other_turns_a_post_into_a_get() {
+    let base = serve(echo).await;
+    let response = client()
+      .request(Method::POST, &format!("{}/old", base), &HashMap::new(), Some(b"payload".to_vec()))
+      .await
+      .unwrap();
+    assert_eq!(response.body, b"GET /new ");
+  }
+
+  #[tokio::test]
+  async fn head_is_not_limited_by_the_announced_length() {
+    let base = serve(echo).await;
+    let client = client();
+    let response = client.head(&format!("{}/large", base), &HashMap::new()).await.unwrap();
+    assert_eq!(response.status, 200);
+    assert!(response.body.is_empty());
+    assert!(client.get(&format!("{}/large", base), &HashMap::new()).await.is_err());
+  }
+
+  #[test]
+  fn repeated_headers_are_joined() {
+    let mut headers = HeaderMap::new();
+    headers.append("cache-control", HeaderValue::from_static("max-age=60"));
+    headers.append("cache-control", HeaderValue::from_static("immutable"));
+    headers.append("set-cookie", HeaderValue::from_static("a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT"));
+    headers.append("set-cookie", HeaderValue::from_static("b=2"));
+    headers.append("x-raw", HeaderValue::from_bytes(b"caf\xe9").unwrap());
+
+    let collected = collect_headers(&headers);
+    assert_eq!(collected["cache-control"], "max-age=60, immutable");
+    assert_eq!(
+      collected["set-cookie"].lines().collect::<Vec<_>>(),
+      vec!["a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT", "b=2"]
+    );
+    assert_eq!(collected["x-raw"], "caf\u{e9}");
+  }
 }
@@ -0,0 +1,110 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rspack_plugin_schemes::{HttpClient, HttpResponse};
+use sha2::{Digest, Sha384};
+
+use crate::http_io::{FetchError, FetchReport};
+use crate::lockfile::{compute_integrity, Lockfile};
+
+/// SRI hash of an emitted asset, for `integrity` attributes.
+pub fn sri(content: &[u8]) -> String {
+    format!("sha384-{}", BASE64.encode(Sha384::digest(content)))
+}
+
+/// Checks `content` against `expected`, which may list several hashes separated by spaces as
+/// in an `integrity` attribute; any one of them matching is enough. Returns the actual hash
+/// on a mismatch.
+fn mismatch(expected: &str, content: &[u8]) -> Option<String> {
+    let mut actual = None;
+    for hash in expected.split_whitespace() {
+        // Options such as `?foo` after the hash are allowed by SRI and ignored here
+        let hash = hash.split('?').next().unwrap_or(hash);
+        match compute_integrity(hash, content) {
+            Some(computed) if computed == hash => return None,
+            Some(computed) => actual = Some(computed),
+            None => {}
+        }
+    }
+    Some(actual.unwrap_or_else(|| sri(content)))
+}
+
+/// Verifies the body of every remote module against its expected integrity before the
+/// compiler sees it.
+///
+/// Hashes come from the `remote.integrity` config and, when `lockfile` is set, from the
+/// lockfile. A body not matching one of them fails with [`FetchError::Integrity`]; URLs
+/// without any expected hash pass through.
+pub struct IntegrityHttpClient {
+    inner: Arc<dyn HttpClient>,
+    expected: BTreeMap<String, String>,
+    lockfile: Option<PathBuf>,
+    report: Arc<FetchReport>,
+}
+
+impl IntegrityHttpClient {
+    pub fn new(
+        inner: Arc<dyn HttpClient>,
+        expected: BTreeMap<String, String>,
+        lockfile: Option<PathBuf>,
+        report: Arc<FetchReport>,
+    ) -> Self {
+        Self {
+            inner,
+            expected,
+            lockfile,
+            report,
+        }
+    }
+
+    fn expected_for(&self, url: &str) -> Vec<String> {
+        let mut expected: Vec<String> = self.expected.get(url).cloned().into_iter().collect();
+        if let Some(lockfile) = self.lockfile.as_deref().and_then(|path| Lockfile::load(path).ok()) {
+            expected.extend(
+                lockfile
+                    .modules()
+                    .into_iter()
+                    .filter(|module| module.url == url || module.resolved.as_deref() == Some(url))
+                    .filter_map(|module| module.integrity),
+            );
+        }
+        expected
+    }
+}
+
+impl std::fmt::Debug for IntegrityHttpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IntegrityHttpClient")
+            .field("expected", &self.expected)
+            .field("lockfile", &self.lockfile)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl HttpClient for IntegrityHttpClient {
+    async fn get(&self, url: &str, headers: &HashMap<String, String>) -> Result<HttpResponse> {
+        let response = self.inner.get(url, headers).await?;
+        // Redirects and revalidations have no body to check
+        if response.status != 200 {
+            return Ok(response);
+        }
+        for expected in self.expected_for(url) {
+            if let Some(actual) = mismatch(&expected, &response.body) {
+                let error = FetchError::Integrity {
+                    url: url.to_string(),
+                    expected,
+                    actual,
+                };
+                self.report.record_failure(error.clone());
+                return Err(error.into());
+            }
+        }
+        Ok(response)
+    }
+}
@@ -0,0 +1,34 @@
+//! Compiles JavaScript entries with rspack, from local files, remote URLs, data URIs or
+//! sources held in memory.
+//!
+//! [`EdgeCompiler`] is the entry point for embedding compilation in another service. The
+//! modules below it are also what the HTTP server binary is built from.
+
+mod macros;
+
+pub mod bundle_store;
+pub mod compile_cache;
+pub mod compile_error;
+pub mod compile_request;
+pub mod compiler;
+pub mod compiler_pool;
+pub mod config;
+pub mod edge_compile;
+pub mod events;
+pub mod http_cache;
+pub mod http_fixtures;
+pub mod http_io;
+pub mod integrity;
+pub mod lockfile;
+pub mod memory_fs;
+pub mod remote_policy;
+pub mod response;
+pub mod system_fs;
+pub mod watcher;
+
+pub use compile_error::{CompileDiagnostic, CompileError};
+pub use compiler::{EdgeCompiler, EdgeCompilerBuilder};
+pub use config::EdgeCompileConfig;
+pub use edge_compile::{CompileInput, CompileOutput, DefaultPlugin, PluginFactory, PreparedCompilation, VirtualSources};
+pub use memory_fs::MockFileSystem;
+pub use system_fs::RealFileSystem;
@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context as _};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::compile_error::CompileError;
+use crate::config::{EdgeCompileConfig, FetchMode};
+use crate::edge_compile::{self, CompileInput};
+use crate::http_cache;
+
+/// One locked remote module as `HttpUriPlugin` recorded it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockedModule {
+    pub url: String,
+    /// Where the URL redirected to, when it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<String>,
+    /// Subresource-integrity style hash, e.g. `sha512-...`. Absent for `no-cache` entries.
+    pub integrity: Option<String>,
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VerifyStatus {
+    /// The cached content matches the locked integrity.
+    Ok,
+    Mismatch,
+    /// Nothing is cached for the URL, so it will be fetched again.
+    NotCached,
+    /// The entry is locked as `no-cache` and has nothing to verify.
+    NoCache,
+    /// The integrity uses an algorithm other than sha256, sha384 or sha512.
+    UnsupportedIntegrity,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Verification {
+    pub url: String,
+    pub status: VerifyStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<String>,
+}
+
+/// Body of `POST /lockfile/prune` and `POST /lockfile/upgrade`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct LockfileRequest {
+    /// URLs to upgrade.
+    pub urls: Vec<String>,
+    /// Entries to compile; pruning needs at least one.
+    pub entries: Vec<String>,
+}
+
+/// The lockfile `HttpUriPlugin` reads and writes next to its cache.
+///
+/// The file maps every remote URL to `{ resolved?, integrity, contentType }` or to the string
+/// `"no-cache"`; any other top-level key (such as `version`) is kept as is. `HttpUriPlugin`
+/// writes the file during compilations, so editing it while compiling may lose changes.
+pub struct Lockfile {
+    path: PathBuf,
+    content: Map<String, Value>,
+}
+
+impl Lockfile {
+    /// Reads the lockfile; a missing file is an empty lockfile.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = match std::fs::read(path) {
+            Ok(content) => serde_json::from_slice(&content)
+                .with_context(|| format!("invalid lockfile {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Map::new(),
+            Err(err) => return Err(err).with_context(|| format!("failed to read lockfile {}", path.display())),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            content,
+        })
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_vec_pretty(&self.content).expect("lockfile is always serializable");
+        let tmp = http_cache::temp_path(&self.path);
+        let written = std::fs::write(&tmp, content).and_then(|_| std::fs::rename(&tmp, &self.path));
+        if written.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        written.with_context(|| format!("failed to write lockfile {}", self.path.display()))
+    }
+
+    pub fn modules(&self) -> Vec<LockedModule> {
+        self.content
+            .iter()
+            .filter(|(url, _)| is_remote(url))
+            .map(|(url, entry)| {
+                let field = |name: &str| entry.get(name).and_then(Value::as_str).map(str::to_string);
+                LockedModule {
+                    url: url.clone(),
+                    resolved: field("resolved"),
+                    integrity: field("integrity"),
+                    content_type: field("contentType"),
+                }
+            })
+            .collect()
+    }
+
+    /// Checks every entry against the content in the HTTP cache under `http_cache_dir`.
+    pub async fn verify(&self, http_cache_dir: &Path) -> Vec<Verification> {
+        let mut verifications = Vec::new();
+        for module in self.modules() {
+            let verification = |status, actual| Verification {
+                url: module.url.clone(),
+                status,
+                actual,
+            };
+            let Some(integrity) = &module.integrity else {
+                verifications.push(verification(VerifyStatus::NoCache, None));
+                continue;
+            };
+            let fetched = module.resolved.as_deref().unwrap_or(&module.url);
+            let Some(body) = http_cache::cached_body(http_cache_dir, fetched).await else {
+                verifications.push(verification(VerifyStatus::NotCached, None));
+                continue;
+            };
+            verifications.push(match compute_integrity(integrity, &body) {
+                Some(actual) if actual == *integrity => verification(VerifyStatus::Ok, None),
+                Some(actual) => verification(VerifyStatus::Mismatch, Some(actual)),
+                None => verification(VerifyStatus::UnsupportedIntegrity, None),
+            });
+        }
+        verifications
+    }
+
+    /// Drops every entry whose URL is not in `used` and returns the dropped URLs.
+    pub fn prune(&mut self, used: &HashSet<String>) -> Vec<String> {
+        let unused: Vec<String> = self
+            .content
+            .keys()
+            .filter(|url| is_remote(url) && !used.contains(*url))
+            .cloned()
+            .collect();
+        self.remove(&unused)
+    }
+
+    /// Drops the entries of `urls` and returns the ones that were locked.
+    pub fn remove(&mut self, urls: &[String]) -> Vec<String> {
+        urls.iter().filter(|url| self.content.remove(*url).is_some()).cloned().collect()
+    }
+}
+
+fn is_remote(key: &str) -> bool {
+    key.starts_with("http://") || key.starts_with("https://")
+}
+
+/// Hashes `content` with the algorithm `integrity` names, in the same `<algo>-<base64>` form.
+pub fn compute_integrity(integrity: &str, content: &[u8]) -> Option<String> {
+    let (algorithm, _) = integrity.split_once('-')?;
+    let digest = match algorithm {
+        "sha256" => Sha256::digest(content).to_vec(),
+        "sha384" => Sha384::digest(content).to_vec(),
+        "sha512" => Sha512::digest(content).to_vec(),
+        _ => return None,
+    };
+    Some(format!("{}-{}", algorithm, BASE64.encode(digest)))
+}
+
+/// Remote modules the given entries depend on, found by compiling them.
+pub async fn used_remote_modules(entries: &[String], config: &EdgeCompileConfig) -> Result<HashSet<String>, CompileError> {
+    let mut used = HashSet::new();
+    for entry in entries {
+        let output = edge_compile::compile(CompileInput::Entry(Some(entry.clone())), config).await?;
+        used.extend(output.remote_modules);
+    }
+    Ok(used)
+}
+
+/// Forgets `urls` in the lockfile and the HTTP cache, then compiles `entries` online so the
+/// current versions get fetched and locked again. Without entries they are locked again by
+/// the next online compilation using them. Returns the URLs that were locked before.
+pub async fn upgrade(
+    lockfile_path: &Path,
+    http_cache_dir: &Path,
+    urls: &[String],
+    entries: &[String],
+    config: &EdgeCompileConfig,
+) -> Result<Vec<String>, CompileError> {
+    let mut lockfile = Lockfile::load(lockfile_path).map_err(internal)?;
+    let upgraded = lockfile.remove(urls);
+    lockfile.save().map_err(internal)?;
+    for url in urls {
+        http_cache::evict(http_cache_dir, url).await;
+    }
+
+    let mut config = config.clone();
+    config.http.mode = FetchMode::Online;
+    used_remote_modules(entries, &config).await?;
+    Ok(upgraded)
+}
+
+/// Compiles `entries` and drops every locked URL none of them uses. Returns the dropped URLs.
+///
+/// Without entries every URL would count as unused, so at least one is required.
+pub async fn prune(lockfile_path: &Path, entries: &[String], config: &EdgeCompileConfig) -> Result<Vec<String>, CompileError> {
+    if entries.is_empty() {
+        return Err(CompileError::BadRequest("`entries` lists no entry to prune against".to_string()));
+    }
+    let used = used_remote_modules(entries, config).await?;
+    let mut lockfile = Lockfile::load(lockfile_path).map_err(internal)?;
+    let pruned = lockfile.prune(&used);
+    lockfile.save().map_err(internal)?;
+    Ok(pruned)
+}
+
+pub fn internal(err: anyhow::Error) -> CompileError {
+    CompileError::Internal(format!("{:#}", err))
+}
+
+/// `lockfile list|verify|prune <entry>...|upgrade <url>... [--entry <entry>]...`
+pub async fn run_cli(args: &[String], config: &EdgeCompileConfig) -> anyhow::Result<()> {
+    let cwd = std::env::current_dir()?;
+    let lockfile_path = edge_compile::lockfile_location(&cwd);
+    let http_cache_dir = http_cache::http_cache_location(&edge_compile::cache_location(&cwd));
+    let (command, rest) = args.split_first().map_or(("list", &[][..]), |(command, rest)| (command.as_str(), rest));
+    match command {
+        "list" => {
+            for module in Lockfile::load(&lockfile_path)?.modules() {
+                println!(
+                    "{}\t{}\t{}",
+                    module.url,
+                    module.integrity.as_deref().unwrap_or("no-cache"),
+                    module.content_type.as_deref().unwrap_or("-")
+                );
+            }
+        }
+        "verify" => {
+            let verifications = Lockfile::load(&lockfile_path)?.verify(&http_cache_dir).await;
+            let mut failed = false;
+            for verification in &verifications {
+                failed |= verification.status == VerifyStatus::Mismatch;
+                println!("{:?}\t{}", verification.status, verification.url);
+            }
+            if failed {
+                bail!("lockfile does not match the cached content");
+            }
+        }
+        "prune" => {
+            for url in prune(&lockfile_path, rest, config).await? {
+                println!("pruned {}", url);
+            }
+        }
+        "upgrade" => {
+            let mut urls = Vec::new();
+            let mut entries = Vec::new();
+            let mut args = rest.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--entry" => entries.extend(args.next().cloned()),
+                    _ => urls.push(arg.clone()),
+                }
+            }
+            if urls.is_empty() {
+                bail!("usage: lockfile upgrade <url>... [--entry <entry>]...");
+            }
+            for url in upgrade(&lockfile_path, &http_cache_dir, &urls, &entries, config).await? {
+                println!("upgraded {}", url);
+            }
+        }
+        other => bail!("unknown lockfile command `{}`, expected list, verify, prune or upgrade", other),
+    }
+    Ok(())
+}
@@ -1,61 +1,482 @@
-#![allow(warnings)]
-
 use std::convert::Infallible;
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use bytes::Bytes;
-use http_body_util::Full;
+use http_body_util::{BodyExt, Full, Limited, StreamBody};
+use http_body_util::LengthLimitError;
+use http_body_util::combinators::UnsyncBoxBody;
+use hyper::body::Incoming;
+use hyper::http::request::Parts;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
-use hyper::{Request, Response};
+use hyper::header::{ACCEPT, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use hyper::{Method, Request, Response, StatusCode};
 use tokio::net::TcpListener;
 use hyper_util::rt::TokioIo;
 use std::collections::HashMap;
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{mpsc, Mutex};
 use url::form_urlencoded;
-mod edge_compile;
-mod memory_fs;
-mod system_fs;
-mod macros;
-mod http_io;
+use rspack_rust_api::{
+    bundle_store, compile_cache, compile_error, compile_request, compiler_pool, config, edge_compile, events,
+    http_cache, integrity, lockfile, response, watcher,
+};
+
+use bundle_store::BundleStore;
+use compile_cache::{CacheKey, CompileCache};
+use compiler_pool::CompilerPool;
+use compile_error::CompileError;
+use config::EdgeCompileConfig;
+use edge_compile::CompileInput;
+use events::{EventHub, ServerEvent};
+use response::{CompileResponse, EntryKind, ErrorResponse};
+use watcher::{WatchTarget, Watcher};
+
+// How many compilations stay addressable under `/bundle/<id>/` before the oldest is dropped
+const BUNDLE_STORE_CAPACITY: usize = 64;
+
+// How many compilation outputs are kept for repeated requests
+const COMPILE_CACHE_CAPACITY: usize = 128;
+
+// How many warm compilers are kept alive for incremental rebuilds
+const COMPILER_POOL_CAPACITY: usize = 16;
 
+// How many events a slow `/events` client may fall behind before it skips some
+const EVENT_HUB_CAPACITY: usize = 64;
+
+// How long to wait for more file changes before rebuilding, editors tend to write in bursts
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(50);
+
+// Plain responses and the `/events` stream share one body type
+type ResponseBody = UnsyncBoxBody<Bytes, Infallible>;
+
+fn full(body: impl Into<Bytes>) -> ResponseBody {
+    Full::new(body.into()).boxed_unsync()
+}
+
+// State shared by every connection
+struct AppState {
+    config: EdgeCompileConfig,
+    bundles: BundleStore,
+    cache: CompileCache,
+    compilers: CompilerPool,
+    lockfile: PathBuf,
+    // Held while `/lockfile/prune` or `/lockfile/upgrade` rewrites the lockfile, so concurrent
+    // requests never drop each other's changes
+    lockfile_edits: Mutex<()>,
+    http_cache_dir: PathBuf,
+    watcher: Watcher,
+    events: EventHub,
+}
 
 // An async function that consumes a request, executes the rspack file, and returns a response.
-async fn handle_request(req: Request<impl hyper::body::Body>) -> Result<Response<Full<Bytes>>, Infallible> {
-    if req.uri().path() == "/favicon.ico" {
-        return Ok(Response::new(Full::new(Bytes::new())));
+async fn handle_request(req: Request<Incoming>, state: Arc<AppState>) -> Result<Response<ResponseBody>, Infallible> {
+    let (parts, body) = req.into_parts();
+    if parts.uri.path() == "/favicon.ico" {
+        return Ok(Response::new(full(Bytes::new())));
+    }
+    if let Some(asset_path) = parts.uri.path().strip_prefix("/bundle/") {
+        return Ok(serve_bundle_asset(&state.bundles, asset_path, &parts).await);
+    }
+    if parts.uri.path() == "/events" {
+        return Ok(event_stream(&state.events));
+    }
+    if parts.uri.path() == "/lockfile" || parts.uri.path().starts_with("/lockfile/") {
+        return Ok(match handle_lockfile(&state, &parts, body).await {
+            Ok(response) => response,
+            Err(err) => error_response(&err, true),
+        });
     }
 
     let start_time = Instant::now();
 
     // Parse the query parameters
-    let query_params: HashMap<_, _> = req.uri().query().map(|v| {
-        form_urlencoded::parse(v.as_bytes()).into_iter().collect()
-    }).unwrap_or_else(HashMap::new);
-    
-    // Log the query parameters for debugging
-    dbg!(req.uri().clone()); // Ensure dbg! does not interfere with the return type
-
-    // Get the entry parameter
-    let entry = query_params.get("entry").cloned().unwrap_or_else(|| "".to_string().into());
-    // Pass the entry parameter to the compile function
-    let result = edge_compile::compile(Some(entry.clone().to_string())).await;
+    let query_params: HashMap<_, _> = parts.uri.query().map(|v| {
+        form_urlencoded::parse(v.as_bytes()).collect()
+    }).unwrap_or_default();
+    log::debug!("Compile request: {}", parts.uri);
+
+    // JSON is opted into either through content negotiation or an explicit `format=json`
+    let wants_json = query_params.get("format").is_some_and(|format| format == "json")
+        || parts.headers
+            .get(ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains("application/json"));
+
+    let config = match request_config(&state.config, &query_params) {
+        Ok(config) => config,
+        Err(err) => return Ok(error_response(&err, wants_json)),
+    };
+
+    let watch = query_params.get("watch").is_some_and(|watch| watch == "1" || watch == "true");
+
+    let (input, config) = if parts.method == Method::POST && parts.uri.path() == "/compile" {
+        // `POST /compile` carries the sources (and optionally more config) in the body
+        let content_type = parts.headers.get(CONTENT_TYPE).and_then(|value| value.to_str().ok());
+        let request_body = match read_compile_body(content_type, body).await {
+            Ok(request_body) => request_body,
+            Err(err) => return Ok(error_response(&err, wants_json)),
+        };
+        let config = match request_body.config {
+            Some(overrides) => match config.with_request_overrides(&overrides) {
+                Ok(config) => config,
+                Err(err) => return Ok(error_response(&err, wants_json)),
+            },
+            None => config,
+        };
+        (CompileInput::Virtual(request_body.sources), config)
+    } else {
+        // Get the entry parameter
+        let entry = query_params.get("entry").map(|entry| entry.to_string()).unwrap_or_default();
+        (CompileInput::Entry(Some(entry)), config)
+    };
+    // Hot clients only get updates for entries that are watched
+    let watch_entry = match &input {
+        CompileInput::Entry(entry) if watch || config.hot => entry.clone(),
+        _ => None,
+    };
+    // Identical inputs whose dependencies did not change are answered from the cache
+    let cache_key = CacheKey::new(&input, &config);
+    let (output, cached, rebuild) = match state.cache.get(&cache_key) {
+        Some(output) => (output, true, None),
+        None => {
+            let build_started = SystemTime::now();
+            // Entries go through a warm compiler that rebuilds incrementally, submitted
+            // sources are compiled once
+            let result = match input {
+                CompileInput::Entry(_) => {
+                    let result = state.compilers.compile(cache_key.clone(), input, &config).await;
+                    unwatch_evicted(&state);
+                    result.map(|(output, rebuild)| (output, Some(rebuild)))
+                }
+                CompileInput::Virtual(_) => edge_compile::compile(input, &config)
+                    .await
+                    .map(|output| (Arc::new(output), None)),
+            };
+            match result {
+                Ok((output, rebuild)) => {
+                    state.cache.insert(cache_key.clone(), output.clone(), build_started);
+                    (output, false, rebuild)
+                }
+                Err(err) => return Ok(error_response(&err, wants_json)),
+            }
+        }
+    };
     let duration = start_time.elapsed();
+    // The bundle of a cached output may have been evicted in the meantime
+    if !state.bundles.contains(&output.id).await {
+        state.bundles.insert(&output.id, &output.files).await;
+    }
+    // Only local entries have dependencies on disk worth watching
+    if let Some(entry) = watch_entry {
+        if EntryKind::of(&output.entry) == EntryKind::Local {
+            let target = WatchTarget { key: cache_key, entry, config: config.clone() };
+            if let Err(err) = state.watcher.watch(target, &output) {
+                log::warn!("Error watching {}: {:?}", output.entry, err);
+            }
+        }
+    }
+
+    if wants_json {
+        let body = serde_json::to_vec(&CompileResponse::new(duration, &output, cached, rebuild))
+            .expect("compile response is always serializable");
+        return Ok(Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(full(Bytes::from(body)))
+            .unwrap());
+    }
 
     // Format the response body with compile time and file contents
-    let mut response_body = format!("Compile time: {:?}\n", duration);
-    for (path, content) in result {
+    let mut response_body = format!("Compile time: {:?}{}\n", duration, if cached { " (cached)" } else { "" });
+    response_body.push_str(&format!("Bundle: {}\n", response::bundle_url(&output.id, "")));
+    if let Some(rebuild) = &rebuild {
+        response_body.push_str(&format!("Build: {:?} in {:.2}ms\n", rebuild.kind, rebuild.duration_ms));
+    }
+    for warning in &output.warnings {
+        response_body.push_str(&format!("Warning: {}\n", warning));
+    }
+    for chain in &output.redirects {
+        response_body.push_str(&format!("Redirect: {} -> {}\n", chain.url, chain.resolved()));
+    }
+    for (path, content) in &output.files {
         response_body.push_str(&format!("File path: {}\n", path));
-        response_body.push_str(&format!("File content: {}\n", String::from_utf8_lossy(&content)));
+        response_body.push_str(&format!("File integrity: {}\n", integrity::sri(content)));
+        response_body.push_str(&format!("File content: {}\n", String::from_utf8_lossy(content)));
+    }
+
+    Ok(Response::new(full(Bytes::from(response_body))))
+}
+
+// Serves `GET /bundle/<id>/<path>` from the assets of a stored compilation.
+async fn serve_bundle_asset(bundles: &BundleStore, asset_path: &str, parts: &Parts) -> Response<ResponseBody> {
+    if parts.method != Method::GET && parts.method != Method::HEAD {
+        return Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .body(full(Bytes::new()))
+            .unwrap();
+    }
+    let (asset, cache_control) = match asset_path.split_once('/') {
+        Some((id, path)) => match bundles.get(id, path).await {
+            // Bundle ids are unique per compilation, so an asset never changes under its URL
+            Some(asset) => (Some(asset), "public, max-age=31536000, immutable"),
+            // Hot updates are requested relative to whichever bundle the client started from
+            None => (bundles.get_hot_update(path).await, "no-cache"),
+        },
+        None => (None, "no-cache"),
+    };
+    let Some(asset) = asset else {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(full(Bytes::from_static(b"Asset not found\n")))
+            .unwrap();
+    };
+
+    let builder = Response::builder()
+        .header(ETAG, &asset.etag)
+        .header(CACHE_CONTROL, cache_control);
+    let not_modified = parts.headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == asset.etag || tag.trim() == "*"));
+    if not_modified {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(full(Bytes::new()))
+            .unwrap();
     }
 
-    Ok(Response::new(Full::new(Bytes::from(response_body))))
+    let body = if parts.method == Method::HEAD { Bytes::new() } else { asset.content.clone() };
+    builder
+        .header(CONTENT_TYPE, asset.content_type)
+        .header(CONTENT_LENGTH, asset.content.len())
+        .body(full(body))
+        .unwrap()
+}
+
+// Streams server events to the client as Server-Sent Events until it disconnects.
+fn event_stream(events: &EventHub) -> Response<ResponseBody> {
+    Response::builder()
+        .header(CONTENT_TYPE, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache")
+        .body(StreamBody::new(events.subscribe()).boxed_unsync())
+        .unwrap()
+}
+
+// Rebuilds watched entries when their dependencies change and tells `/events` clients about it.
+async fn rebuild_watched(state: Arc<AppState>, mut dirty: mpsc::UnboundedReceiver<WatchTarget>) {
+    while let Some(target) = dirty.recv().await {
+        let mut targets = HashMap::new();
+        targets.insert(target.key.clone(), target);
+        // Coalesce the burst of events a single save usually produces
+        while let Ok(Some(target)) = tokio::time::timeout(WATCH_DEBOUNCE, dirty.recv()).await {
+            targets.insert(target.key.clone(), target);
+        }
+
+        for (key, target) in targets {
+            let input = CompileInput::Entry(Some(target.entry.clone()));
+            let build_started = SystemTime::now();
+            let result = state.compilers.compile(key.clone(), input, &target.config).await;
+            unwatch_evicted(&state);
+            match result {
+                Ok((output, rebuild)) => {
+                    state.cache.insert(key, output.clone(), build_started);
+                    state.bundles.insert(&output.id, &output.files).await;
+                    // Dependencies may have been added or removed by the change
+                    let entry = target.entry.clone();
+                    if let Err(err) = state.watcher.watch(target, &output) {
+                        log::warn!("Error watching {}: {:?}", entry, err);
+                    }
+                    state.events.publish(ServerEvent::build(&output, rebuild));
+                }
+                Err(err) => state.events.publish(ServerEvent::error(&target.entry, &err)),
+            }
+        }
+    }
+}
+
+// Evicted compilers are no longer rebuilt when their files change; requesting them watches them again.
+fn unwatch_evicted(state: &AppState) {
+    for key in state.compilers.take_evicted() {
+        if let Err(err) = state.watcher.unwatch(&key) {
+            log::warn!("Error unwatching evicted compiler: {:?}", err);
+        }
+    }
+}
+
+// `GET /lockfile`, `GET /lockfile/verify`, `POST /lockfile/prune` and `POST /lockfile/upgrade`.
+async fn handle_lockfile(state: &AppState, parts: &Parts, body: Incoming) -> Result<Response<ResponseBody>, CompileError> {
+    let (lockfile_path, http_cache_dir) = (&state.lockfile, &state.http_cache_dir);
+    let body = match (parts.method.as_str(), parts.uri.path()) {
+        ("GET", "/lockfile") => {
+            let lockfile = lockfile::Lockfile::load(lockfile_path).map_err(lockfile::internal)?;
+            serde_json::json!({ "modules": lockfile.modules() })
+        }
+        ("GET", "/lockfile/verify") => {
+            let lockfile = lockfile::Lockfile::load(lockfile_path).map_err(lockfile::internal)?;
+            let verifications = lockfile.verify(http_cache_dir).await;
+            let ok = verifications.iter().all(|verification| verification.status != lockfile::VerifyStatus::Mismatch);
+            serde_json::json!({ "ok": ok, "modules": verifications })
+        }
+        ("POST", "/lockfile/prune") => {
+            let request: lockfile::LockfileRequest = read_json_body(body).await?;
+            let _edit = state.lockfile_edits.lock().await;
+            let pruned = lockfile::prune(lockfile_path, &request.entries, &state.config).await?;
+            serde_json::json!({ "pruned": pruned })
+        }
+        ("POST", "/lockfile/upgrade") => {
+            let request: lockfile::LockfileRequest = read_json_body(body).await?;
+            if request.urls.is_empty() {
+                return Err(CompileError::BadRequest("`urls` lists no URL to upgrade".to_string()));
+            }
+            let _edit = state.lockfile_edits.lock().await;
+            let upgraded = lockfile::upgrade(lockfile_path, http_cache_dir, &request.urls, &request.entries, &state.config).await?;
+            serde_json::json!({ "upgraded": upgraded })
+        }
+        _ => {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(full(Bytes::from_static(b"Not found\n")))
+                .unwrap())
+        }
+    };
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(full(serde_json::to_vec(&body).expect("lockfile response is always serializable")))
+        .unwrap())
+}
+
+// Reads a JSON request body; an empty body is the default value.
+async fn read_json_body<T: serde::de::DeserializeOwned + Default>(body: Incoming) -> Result<T, CompileError> {
+    let body = read_limited_body(body).await?;
+    if body.is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(&body).map_err(|err| CompileError::BadRequest(format!("invalid JSON body: {}", err)))
+}
+
+async fn read_limited_body(body: Incoming) -> Result<Bytes, CompileError> {
+    Ok(Limited::new(body, compile_request::MAX_BODY_SIZE)
+        .collect()
+        .await
+        .map_err(|err| {
+            if err.downcast_ref::<LengthLimitError>().is_some() {
+                CompileError::PayloadTooLarge { limit: compile_request::MAX_BODY_SIZE }
+            } else {
+                CompileError::BadRequest(format!("failed to read request body: {}", err))
+            }
+        })?
+        .to_bytes())
+}
+
+async fn read_compile_body(content_type: Option<&str>, body: Incoming) -> Result<compile_request::CompileRequestBody, CompileError> {
+    let body = read_limited_body(body).await?;
+    compile_request::parse(content_type, body).await
+}
+
+// Applies the per-request overrides: `config` takes a partial JSON config, `mode`, `hot` and `fetch` are shorthands for it.
+fn request_config(base: &EdgeCompileConfig, query_params: &HashMap<std::borrow::Cow<str>, std::borrow::Cow<str>>) -> Result<EdgeCompileConfig, CompileError> {
+    let mut overrides = match query_params.get("config") {
+        Some(raw) => serde_json::from_str(raw)
+            .map_err(|err| CompileError::BadConfig(format!("`config` is not valid JSON: {}", err)))?,
+        None => serde_json::Value::Object(Default::default()),
+    };
+    if let (Some(mode), Some(object)) = (query_params.get("mode"), overrides.as_object_mut()) {
+        object.insert("mode".to_string(), serde_json::Value::String(mode.to_string()));
+    }
+    if let (Some(hot), Some(object)) = (query_params.get("hot"), overrides.as_object_mut()) {
+        object.insert("hot".to_string(), serde_json::Value::Bool(hot == "1" || hot == "true"));
+    }
+    if let (Some(library), Some(object)) = (query_params.get("library"), overrides.as_object_mut()) {
+        let output = object.entry("output").or_insert_with(|| serde_json::Value::Object(Default::default()));
+        if let Some(output) = output.as_object_mut() {
+            output.insert("format".to_string(), serde_json::Value::String(library.to_string()));
+        }
+    }
+    if let (Some(fetch), Some(object)) = (query_params.get("fetch"), overrides.as_object_mut()) {
+        let http = object.entry("http").or_insert_with(|| serde_json::Value::Object(Default::default()));
+        if let Some(http) = http.as_object_mut() {
+            http.insert("mode".to_string(), serde_json::Value::String(fetch.to_string()));
+        }
+    }
+    base.with_request_overrides(&overrides)
+}
+
+// Maps a failed compilation onto its status code, keeping the rspack diagnostics in the body.
+fn error_response(err: &CompileError, wants_json: bool) -> Response<ResponseBody> {
+    let body = if wants_json {
+        serde_json::to_vec(&ErrorResponse::new(err)).expect("error response is always serializable")
+    } else {
+        let mut body = format!("Error: {}\n", err);
+        for diagnostic in err.diagnostics() {
+            body.push_str(&format!("{}\n", diagnostic));
+        }
+        for failure in err.fetch_failures() {
+            body.push_str(&format!("{}\n", failure));
+        }
+        body.into_bytes()
+    };
+    Response::builder()
+        .status(err.status_code())
+        .header(CONTENT_TYPE, if wants_json { "application/json" } else { "text/plain; charset=utf-8" })
+        .body(full(Bytes::from(body)))
+        .unwrap()
 }
 
 #[tokio::main]
 pub async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     pretty_env_logger::init();
 
+    // The base config comes from `--config <path>` or `EDGE_COMPILE_CONFIG`, defaults otherwise
+    let config_path = std::env::args()
+        .skip_while(|arg| arg != "--config")
+        .nth(1)
+        .or_else(|| std::env::var("EDGE_COMPILE_CONFIG").ok())
+        .map(PathBuf::from);
+    let config = match config_path {
+        Some(path) => EdgeCompileConfig::load(&path)?,
+        None => EdgeCompileConfig::default(),
+    };
+
+    // `lockfile <command>` manages the lockfile instead of starting the server
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("lockfile") {
+        let mut command = Vec::new();
+        let mut rest = args[1..].iter();
+        while let Some(arg) = rest.next() {
+            if arg == "--config" {
+                rest.next();
+            } else {
+                command.push(arg.clone());
+            }
+        }
+        lockfile::run_cli(&command, &config).await?;
+        return Ok(());
+    }
+
+    let cwd = std::env::current_dir()?;
+    let (watcher, dirty) = Watcher::new()?;
+    let state = Arc::new(AppState {
+        config,
+        bundles: BundleStore::new(BUNDLE_STORE_CAPACITY),
+        cache: CompileCache::new(
+            NonZeroUsize::new(COMPILE_CACHE_CAPACITY).unwrap(),
+            edge_compile::lockfile_location(&cwd),
+        ),
+        compilers: CompilerPool::new(
+            NonZeroUsize::new(COMPILER_POOL_CAPACITY).unwrap(),
+            edge_compile::lockfile_location(&cwd),
+        ),
+        lockfile: edge_compile::lockfile_location(&cwd),
+        lockfile_edits: Mutex::new(()),
+        http_cache_dir: http_cache::http_cache_location(&edge_compile::cache_location(&cwd)),
+        watcher,
+        events: EventHub::new(EVENT_HUB_CAPACITY),
+    });
+    tokio::task::spawn(rebuild_watched(state.clone(), dirty));
+
     // This address is localhost
     let addr: SocketAddr = ([127, 0, 0, 1], 3001).into();
 
@@ -75,6 +496,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // Use an adapter to access something implementing `tokio::io` traits as if they implement
         // `hyper::rt` IO traits.
         let io = TokioIo::new(tcp);
+        let state = state.clone();
 
         // Spin up a new task in Tokio so we can continue to listen for new TCP connection on the
         // current task without waiting for the processing of the HTTP1 connection we just received
@@ -83,10 +505,10 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             // Handle the connection from the client using HTTP1 and pass any
             // HTTP requests received on that connection to the `handle_request` function
             if let Err(err) = http1::Builder::new()
-                .serve_connection(io, service_fn(handle_request))
+                .serve_connection(io, service_fn(move |req| handle_request(req, state.clone())))
                 .await
             {
-                println!("Error serving connection: {:?}", err);
+                log::error!("Error serving connection: {:?}", err);
             }
         });
     }
@@ -2,78 +2,204 @@
 
 use std::{
     collections::HashMap,
-    path::{Path, PathBuf},
-    sync::Arc,
+    fs::{self, Metadata},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, OnceLock, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
     io::{Error, ErrorKind},
+    time::SystemTime,
 };
 use rspack_paths::Utf8Path;
 use futures::future::BoxFuture;
-use tokio::sync::RwLock as AsyncRwLock;
 use rspack_fs::{
     r#async::{AsyncReadableFileSystem, AsyncWritableFileSystem},
     sync::{ReadableFileSystem, WritableFileSystem},
     Result
 };
 
+/// An in-memory filesystem usable both as rspack's output filesystem and as its input
+/// filesystem (including for `ResolverFactory`).
+///
+/// Paths are normalized lexically on every access. Directories exist if they were created
+/// explicitly or if anything lives below them.
+///
+/// `std::fs::Metadata` cannot be built by hand, so through [`ReadableFileSystem::metadata`]
+/// only `is_file` and `is_dir` are meaningful; size and modification time belong to a real
+/// entry. [`MockFileSystem::stat`] has the synthetic ones.
 #[derive(Clone)]
 pub struct MockFileSystem {
-    pub files: Arc<AsyncRwLock<HashMap<PathBuf, Vec<u8>>>>,
-    pub directories: Arc<AsyncRwLock<HashMap<PathBuf, ()>>>,
+    pub files: Arc<RwLock<HashMap<PathBuf, Vec<u8>>>>,
+    pub directories: Arc<RwLock<HashMap<PathBuf, ()>>>,
+    modified: Arc<RwLock<HashMap<PathBuf, SystemTime>>>,
+    created: SystemTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Directory,
+}
+
+/// Synthetic metadata of an in-memory entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    pub kind: FileKind,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+impl Default for MockFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MockFileSystem {
     pub fn new() -> Self {
-        dbg!("Creating new MockFileSystem");
         Self {
-            files: Arc::new(AsyncRwLock::new(HashMap::new())),
-            directories: Arc::new(AsyncRwLock::new(HashMap::new())),
+            files: Arc::new(RwLock::new(HashMap::new())),
+            directories: Arc::new(RwLock::new(HashMap::new())),
+            modified: Arc::new(RwLock::new(HashMap::new())),
+            created: SystemTime::now(),
+        }
+    }
+
+    /// Adds or replaces a file, e.g. to seed an input filesystem.
+    pub fn insert(&self, path: &Path, content: Vec<u8>) {
+        self.write_file(normalize(path), content);
+    }
+
+    /// Returns the metadata of a file or directory, `None` if it does not exist.
+    pub fn stat(&self, path: &Path) -> Option<FileStat> {
+        let path = normalize(path);
+        let size = read_lock(&self.files).get(&path).map(|content| content.len() as u64);
+        if let Some(size) = size {
+            let modified = read_lock(&self.modified).get(&path).copied().unwrap_or(self.created);
+            return Some(FileStat { kind: FileKind::File, size, modified });
+        }
+        self.is_dir(&path).then_some(FileStat {
+            kind: FileKind::Directory,
+            size: 0,
+            modified: self.created,
+        })
+    }
+
+    /// Removes every file `remove` returns true for.
+    pub fn remove_files(&self, remove: impl Fn(&Path) -> bool) {
+        write_lock(&self.modified).retain(|path, _| !remove(path));
+        write_lock(&self.files).retain(|path, _| !remove(path));
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        let has_dir = read_lock(&self.directories).keys().any(|dir| dir.starts_with(path));
+        has_dir || read_lock(&self.files).keys().any(|file| file.starts_with(path) && file != path)
+    }
+
+    fn write_file(&self, path: PathBuf, data: Vec<u8>) {
+        write_lock(&self.modified).insert(path.clone(), SystemTime::now());
+        write_lock(&self.files).insert(path, data);
+    }
+}
+
+/// Resolves `.` and `..` lexically; `..` never climbs above the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
         }
     }
+    normalized
+}
+
+fn insert_dir_all(directories: &mut HashMap<PathBuf, ()>, dir: &Path) {
+    for ancestor in dir.ancestors().filter(|ancestor| !ancestor.as_os_str().is_empty()) {
+        directories.insert(ancestor.to_path_buf(), ());
+    }
+}
+
+fn not_found() -> Error {
+    Error::new(ErrorKind::NotFound, "No such file or directory")
+}
+
+// Poisoning only means another thread panicked mid-operation; every operation leaves the maps
+// consistent, so the data is still usable.
+fn read_lock<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write_lock<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+// In-memory entries borrow the metadata of one real entry per kind: the running executable
+// for files and the temp directory for directories. Failing to do so is an error of its own,
+// it must not pass for the in-memory entry being missing.
+fn stub_metadata(kind: FileKind) -> std::io::Result<Metadata> {
+    static FILE_STUB: OnceLock<Option<PathBuf>> = OnceLock::new();
+    let stub = match kind {
+        FileKind::File => FILE_STUB
+            .get_or_init(|| std::env::current_exe().ok())
+            .clone()
+            .ok_or_else(|| Error::other("no file to borrow metadata from, the current executable is unknown"))?,
+        FileKind::Directory => std::env::temp_dir(),
+    };
+    let metadata = fs::metadata(&stub)
+        .map_err(|err| Error::other(format!("failed to borrow metadata from {}: {}", stub.display(), err)))?;
+    let matches = match kind {
+        FileKind::File => metadata.is_file(),
+        FileKind::Directory => metadata.is_dir(),
+    };
+    if !matches {
+        return Err(Error::other(format!("{} is not a {:?} to borrow metadata from", stub.display(), kind)));
+    }
+    Ok(metadata)
 }
 
 impl WritableFileSystem for MockFileSystem {
     fn create_dir(&self, dir: &Utf8Path) -> Result<()> {
-        let dir_ref: PathBuf = dir.to_path_buf().into();
-        dbg!("Creating directory: {}", dir_ref.display());
-        let mut directories = self.directories.blocking_write();
+        let dir_ref: PathBuf = normalize(dir.as_std_path());
+        let mut directories = write_lock(&self.directories);
         directories.insert(dir_ref, ());
         Ok(())
     }
 
     fn create_dir_all(&self, dir: &Utf8Path) -> Result<()> {
-        let dir_ref: PathBuf = dir.to_path_buf().into();
-        dbg!("Creating directory recursively: {}", dir_ref.display());
-        let mut directories = self.directories.blocking_write();
-        directories.insert(dir_ref, ());
+        let dir_ref: PathBuf = normalize(dir.as_std_path());
+        let mut directories = write_lock(&self.directories);
+        insert_dir_all(&mut directories, &dir_ref);
         Ok(())
     }
 
     fn write(&self, file: &Utf8Path, data: &[u8]) -> Result<()> {
-        let file_ref: PathBuf = file.to_path_buf().into();
-        dbg!("Writing to file: {}", file_ref.display());
-        let mut files = self.files.blocking_write();
-        files.insert(file_ref, data.to_vec());
+        let file_ref: PathBuf = normalize(file.as_std_path());
+        self.write_file(file_ref, data.to_vec());
         Ok(())
     }
 }
 
 impl ReadableFileSystem for MockFileSystem {
-    fn metadata(&self, _path: &Path) -> std::io::Result<std::fs::Metadata> {
-        unimplemented!()
+    fn metadata(&self, path: &Path) -> std::io::Result<std::fs::Metadata> {
+        let stat = self.stat(path).ok_or_else(not_found)?;
+        stub_metadata(stat.kind)
     }
 
-    fn symlink_metadata(&self, _path: &Path) -> std::io::Result<std::fs::Metadata> {
-        unimplemented!()
+    fn symlink_metadata(&self, path: &Path) -> std::io::Result<std::fs::Metadata> {
+        // There are no symlinks in memory, so this is the same as `metadata`.
+        self.metadata(path)
     }
 
-    fn canonicalize(&self, _path: &Path) -> std::io::Result<PathBuf> {
-        unimplemented!()
+    fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+        let normalized = normalize(path);
+        self.stat(&normalized).map(|_| normalized).ok_or_else(not_found)
     }
 
     fn read(&self, file: &Path) -> std::io::Result<Vec<u8>> {
-        let file_ref: PathBuf = file.to_path_buf();
-        dbg!("Reading file: {}", file_ref.display());
-        let files = self.files.blocking_read();
+        let file_ref: PathBuf = normalize(file);
+        let files = read_lock(&self.files);
         files
             .get(&file_ref)
             .cloned()
@@ -83,57 +209,51 @@ impl ReadableFileSystem for MockFileSystem {
 
 impl AsyncWritableFileSystem for MockFileSystem {
     fn create_dir(&self, dir: &Utf8Path) -> BoxFuture<'_, Result<()>> {
-        let dir_ref: PathBuf = dir.to_path_buf().into();
-        dbg!("Async creating directory: {}", dir_ref.display());
+        let dir_ref: PathBuf = normalize(dir.as_std_path());
         let directories = self.directories.clone();
         Box::pin(async move {
-            let mut directories = directories.write().await;
+            let mut directories = write_lock(&directories);
             directories.insert(dir_ref, ());
             Ok(())
         })
     }
 
     fn create_dir_all(&self, dir: &Utf8Path) -> BoxFuture<'_, Result<()>> {
-        let dir_ref: PathBuf = dir.to_path_buf().into();
-        dbg!("Async creating directory recursively: {}", dir_ref.display());
+        let dir_ref: PathBuf = normalize(dir.as_std_path());
         let directories = self.directories.clone();
         Box::pin(async move {
-            let mut directories = directories.write().await;
-            directories.insert(dir_ref, ());
+            let mut directories = write_lock(&directories);
+            insert_dir_all(&mut directories, &dir_ref);
             Ok(())
         })
     }
 
     fn write(&self, file: &Utf8Path, data: &[u8]) -> BoxFuture<'_, Result<()>> {
-        let file_ref: PathBuf = file.to_path_buf().into();
+        let file_ref: PathBuf = normalize(file.as_std_path());
         let data = data.to_vec();
-        dbg!("Async writing to file: {}", file_ref.display());
-        let files = self.files.clone();
         Box::pin(async move {
-            let mut files = files.write().await;
-            files.insert(file_ref, data);
+            self.write_file(file_ref, data);
             Ok(())
         })
     }
 
     fn remove_file(&self, file: &Utf8Path) -> BoxFuture<'_, Result<()>> {
-        let file_ref: PathBuf = file.to_path_buf().into();
-        dbg!("Async removing file: {}", file_ref.display());
-        let files = self.files.clone();
+        let file_ref: PathBuf = normalize(file.as_std_path());
         Box::pin(async move {
-            let mut files = files.write().await;
+            write_lock(&self.modified).remove(&file_ref);
+            let mut files = write_lock(&self.files);
             files.remove(&file_ref);
             Ok(())
         })
     }
 
     fn remove_dir_all(&self, dir: &Utf8Path) -> BoxFuture<'_, Result<()>> {
-        let dir_ref: PathBuf = dir.to_path_buf().into();
-        dbg!(dir_ref.display());
-        let directories = self.directories.clone();
+        let dir_ref: PathBuf = normalize(dir.as_std_path());
         Box::pin(async move {
-            let mut directories = directories.write().await;
-            directories.remove(&dir_ref);
+            write_lock(&self.directories).retain(|path, _| !path.starts_with(&dir_ref));
+            write_lock(&self.modified).retain(|path, _| !path.starts_with(&dir_ref));
+            let mut files = write_lock(&self.files);
+            files.retain(|path, _| !path.starts_with(&dir_ref));
             Ok(())
         })
     }
@@ -141,15 +261,78 @@ impl AsyncWritableFileSystem for MockFileSystem {
 
 impl AsyncReadableFileSystem for MockFileSystem {
     fn read(&self, file: &Utf8Path) -> BoxFuture<'_, Result<Vec<u8>>> {
-        let file_ref: PathBuf = file.to_path_buf().into();
-        dbg!(file_ref.as_path().display());
+        let file_ref: PathBuf = normalize(file.as_std_path());
         let files = self.files.clone();
         Box::pin(async move {
-            let files = files.read().await;
+            let files = read_lock(&files);
             files
                 .get(&file_ref)
                 .cloned()
                 .ok_or_else(|| rspack_fs::Error::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "File not found")))
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use rspack_paths::Utf8Path;
+
+    use super::*;
+
+    #[test]
+    fn normalizes_paths_lexically() {
+        assert_eq!(normalize(Path::new("/src/./lib/../index.js")), PathBuf::from("/src/index.js"));
+        assert_eq!(normalize(Path::new("/../../src/index.js")), PathBuf::from("/src/index.js"));
+        assert_eq!(normalize(Path::new("src//index.js")), PathBuf::from("src/index.js"));
+    }
+
+    #[test]
+    fn parents_of_files_are_directories() {
+        let fs = MockFileSystem::new();
+        fs.insert(Path::new("/src/lib/a.js"), b"export default 1;".to_vec());
+
+        assert_eq!(fs.stat(Path::new("/src")).map(|stat| stat.kind), Some(FileKind::Directory));
+        assert_eq!(fs.stat(Path::new("/src/lib/")).map(|stat| stat.kind), Some(FileKind::Directory));
+        assert_eq!(fs.stat(Path::new("/src/lib/a.js")).map(|stat| stat.kind), Some(FileKind::File));
+        // Directories are matched by whole components
+        assert_eq!(fs.stat(Path::new("/sr")), None);
+        assert_eq!(fs.stat(Path::new("/src/lib/a")), None);
+
+        WritableFileSystem::create_dir_all(&fs, Utf8Path::new("/out/assets")).unwrap();
+        assert_eq!(fs.stat(Path::new("/out")).map(|stat| stat.kind), Some(FileKind::Directory));
+    }
+
+    #[test]
+    fn stat_reports_size_and_modification_time() {
+        let fs = MockFileSystem::new();
+        let before = SystemTime::now();
+        fs.insert(Path::new("/a.js"), b"export default 1;".to_vec());
+
+        let stat = fs.stat(Path::new("/./a.js")).unwrap();
+        assert_eq!(stat.size, 17);
+        assert!(stat.modified >= before);
+        assert_eq!(fs.stat(Path::new("/")).map(|stat| stat.size), Some(0));
+    }
+
+    #[test]
+    fn canonicalize_normalizes_existing_paths() {
+        let fs = MockFileSystem::new();
+        fs.insert(Path::new("/src/a.js"), Vec::new());
+
+        assert_eq!(fs.canonicalize(Path::new("/src/../src/./a.js")).unwrap(), PathBuf::from("/src/a.js"));
+        assert_eq!(fs.canonicalize(Path::new("/src/")).unwrap(), PathBuf::from("/src"));
+        let missing = fs.canonicalize(Path::new("/src/b.js")).unwrap_err();
+        assert_eq!(missing.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn metadata_tells_files_from_directories() {
+        let fs = MockFileSystem::new();
+        fs.insert(Path::new("/src/a.js"), Vec::new());
+
+        assert!(fs.metadata(Path::new("/src/a.js")).unwrap().is_file());
+        assert!(fs.metadata(Path::new("/src")).unwrap().is_dir());
+        assert_eq!(fs.metadata(Path::new("/src/b.js")).unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(ReadableFileSystem::read(&fs, Path::new("/src/./a.js")).unwrap(), Vec::<u8>::new());
+    }
+}
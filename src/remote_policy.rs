@@ -0,0 +1,392 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use rspack_plugin_schemes::{HttpClient, HttpResponse};
+use url::{Host, Url};
+
+use crate::compile_error::{CompileDiagnostic, CompileError};
+use crate::config::{RemoteConfig, UrlPattern};
+
+/// Prefix of the error a denied fetch fails with, so the diagnostic can be classified.
+pub const DENIED_MESSAGE: &str = "denied by remote policy";
+
+enum UrlMatcher {
+    Prefix(String),
+    Regex(Regex),
+}
+
+impl UrlMatcher {
+    fn matches(&self, url: &str) -> bool {
+        match self {
+            UrlMatcher::Prefix(prefix) => url.starts_with(prefix.as_str()),
+            UrlMatcher::Regex(regex) => regex.is_match(url),
+        }
+    }
+}
+
+/// Decides which remote URLs may be fetched, both for entries and for imports.
+///
+/// Only `http` and `https` are ever allowed. A URL must match the allow-list (when it is not
+/// empty) and must not match the deny-list. Unless private networks are allowed, hosts that
+/// are loopback, private, link-local or otherwise non-public addresses are rejected as well;
+/// host names that resolve to such addresses are refused by [`PublicResolver`].
+pub struct RemotePolicy {
+    allow: Vec<UrlMatcher>,
+    deny: Vec<UrlMatcher>,
+    allow_private_networks: bool,
+}
+
+impl RemotePolicy {
+    pub fn new(config: &RemoteConfig) -> Result<Self, CompileError> {
+        let matchers = |patterns: &[UrlPattern]| {
+            patterns
+                .iter()
+                .map(|pattern| match pattern {
+                    UrlPattern::Prefix(prefix) => Ok(UrlMatcher::Prefix(prefix.clone())),
+                    UrlPattern::Regex { regex } => Regex::new(regex)
+                        .map(UrlMatcher::Regex)
+                        .map_err(|err| CompileError::BadConfig(format!("invalid remote URL pattern `{}`: {}", regex, err))),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Self {
+            allow: matchers(&config.allow)?,
+            deny: matchers(&config.deny)?,
+            allow_private_networks: config.allow_private_networks,
+        })
+    }
+
+    /// Checks everything that can be decided from the URL alone.
+    pub fn check_url(&self, url: &str) -> Result<Url, String> {
+        let parsed = Url::parse(url).map_err(|err| format!("invalid URL: {}", err))?;
+        if parsed.scheme() != "http" && parsed.scheme() != "https" {
+            return Err(format!("scheme `{}` is not allowed", parsed.scheme()));
+        }
+        if self.deny.iter().any(|matcher| matcher.matches(url)) {
+            return Err("URL is on the deny-list".to_string());
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|matcher| matcher.matches(url)) {
+            return Err("URL is not on the allow-list".to_string());
+        }
+        if !self.allow_private_networks {
+            match parsed.host() {
+                Some(Host::Ipv4(ip)) if !is_public(IpAddr::V4(ip)) => {
+                    return Err(format!("{} is not a public address", ip));
+                }
+                Some(Host::Ipv6(ip)) if !is_public(IpAddr::V6(ip)) => {
+                    return Err(format!("{} is not a public address", ip));
+                }
+                Some(Host::Domain(domain)) if is_local_domain(domain) => {
+                    return Err(format!("{} is a local host name", domain));
+                }
+                None => return Err("URL has no host".to_string()),
+                _ => {}
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Like [`RemotePolicy::check_url`], for callers that only need to know whether the URL
+    /// may be fetched. Where the host resolves to is checked on connect by [`PublicResolver`].
+    pub fn check(&self, url: &str) -> Result<(), String> {
+        self.check_url(url).map(|_| ())
+    }
+
+    pub fn allows_private_networks(&self) -> bool {
+        self.allow_private_networks
+    }
+
+    /// Rejects the entry up front when it is a remote URL the policy does not allow.
+    pub fn check_entry(&self, entry: &str) -> Result<(), CompileError> {
+        if !entry.starts_with("http://") && !entry.starts_with("https://") {
+            return Ok(());
+        }
+        self.check(entry).map_err(|reason| {
+            CompileError::RemoteDenied(vec![CompileDiagnostic {
+                title: "RemoteDenied".to_string(),
+                message: format!("entry {} {}: {}", entry, DENIED_MESSAGE, reason),
+                module_identifier: None,
+                loc: None,
+                file: None,
+            }])
+        })
+    }
+}
+
+/// Resolves host names for the network client, dropping every non-public address.
+///
+/// Filtering where the connection is made, rather than looking the host up beforehand, leaves
+/// no window for the name to resolve differently between the check and the connect. Proxy
+/// hosts are exempt: they are configured by the operator, and a request that goes through a
+/// proxy never resolves the remote host locally.
+pub struct PublicResolver {
+    proxy_hosts: HashSet<String>,
+}
+
+impl PublicResolver {
+    pub fn new(proxy_hosts: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            proxy_hosts: proxy_hosts.into_iter().map(|host| host.to_ascii_lowercase()).collect(),
+        }
+    }
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let is_proxy = self.proxy_hosts.contains(&host.to_ascii_lowercase());
+        Box::pin(resolve_public(host, is_proxy))
+    }
+}
+
+async fn resolve_public(host: String, is_proxy: bool) -> Result<Addrs, Box<dyn std::error::Error + Send + Sync>> {
+    // The port is replaced by the one of the URL when connecting.
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+    if is_proxy {
+        return Ok(Box::new(addresses.into_iter()));
+    }
+    let public: Vec<SocketAddr> = addresses.iter().copied().filter(|address| is_public(address.ip())).collect();
+    if public.is_empty() {
+        let message = match addresses.first() {
+            Some(address) => format!("{} resolves to {}, which is not a public address", host, address.ip()),
+            None => format!("{} did not resolve to any address", host),
+        };
+        return Err(format!("{}: {}", DENIED_MESSAGE, message).into());
+    }
+    Ok(Box::new(public.into_iter()))
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(embedded) => is_public_v4(embedded),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 address reaches through, for the ranges that carry one:
+/// IPv4-mapped `::ffff:0:0/96`, IPv4-compatible `::/96`, NAT64 `64:ff9b::/96` and
+/// `64:ff9b:1::/48`, and 6to4 `2002::/16`.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let [.., a, b, c, d] = ip.octets();
+    let last = Ipv4Addr::new(a, b, c, d);
+    match ip.segments() {
+        [0, 0, 0, 0, 0, 0xffff, _, _] | [0, 0, 0, 0, 0, 0, _, _] => Some(last),
+        [0x64, 0xff9b, 0, 0, 0, 0, _, _] | [0x64, 0xff9b, 1, ..] => Some(last),
+        [0x2002, high, low, ..] => {
+            let [a, b] = high.to_be_bytes();
+            let [c, d] = low.to_be_bytes();
+            Some(Ipv4Addr::new(a, b, c, d))
+        }
+        _ => None,
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8 "this network" and 100.64.0.0/10 carrier-grade NAT
+        || a == 0
+        || (a == 100 && (b & 0xc0) == 64)
+        // 192.0.0.0/24 protocol assignments and 198.18.0.0/15 benchmarking
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b & 0xfe) == 18)
+        // 240.0.0.0/4 reserved, which includes the broadcast address
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let [first, second, ..] = ip.segments();
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // fc00::/7 unique local, fe80::/10 link-local and fec0::/10 site-local
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || (first & 0xffc0) == 0xfec0
+        // 2001:db8::/32 documentation
+        || (first == 0x2001 && second == 0x0db8))
+}
+
+fn is_local_domain(domain: &str) -> bool {
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    domain == "localhost" || domain.ends_with(".localhost") || domain.ends_with(".local")
+}
+
+/// Wraps the client `HttpUriPlugin` fetches with, refusing every URL the policy denies.
+pub struct PolicyHttpClient {
+    inner: Arc<dyn HttpClient>,
+    policy: Arc<RemotePolicy>,
+}
+
+impl PolicyHttpClient {
+    pub fn new(inner: Arc<dyn HttpClient>, policy: Arc<RemotePolicy>) -> Self {
+        Self { inner, policy }
+    }
+}
+
+impl std::fmt::Debug for PolicyHttpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PolicyHttpClient").finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl HttpClient for PolicyHttpClient {
+    async fn get(&self, url: &str, headers: &HashMap<String, String>) -> Result<HttpResponse> {
+        if let Err(reason) = self.policy.check(url) {
+            anyhow::bail!("{} {}: {}", url, DENIED_MESSAGE, reason);
+        }
+        self.inner.get(url, headers).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(config: RemoteConfig) -> RemotePolicy {
+        RemotePolicy::new(&config).unwrap()
+    }
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn only_http_and_https_are_allowed() {
+        let policy = policy(RemoteConfig::default());
+        assert!(policy.check_url("https://example.com/a.js").is_ok());
+        assert!(policy.check_url("http://example.com/a.js").is_ok());
+        assert!(policy.check_url("file:///etc/passwd").is_err());
+        assert!(policy.check_url("ftp://example.com/a.js").is_err());
+        assert!(policy.check_url("not a url").is_err());
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let policy = policy(RemoteConfig {
+            allow: vec![
+                UrlPattern::Prefix("https://esm.sh/".to_string()),
+                UrlPattern::Regex { regex: r"^https://cdn\.example\.com/".to_string() },
+            ],
+            deny: vec![UrlPattern::Prefix("https://esm.sh/evil".to_string())],
+            ..RemoteConfig::default()
+        });
+        assert!(policy.check_url("https://esm.sh/react").is_ok());
+        assert!(policy.check_url("https://cdn.example.com/a.js").is_ok());
+        assert!(policy.check_url("https://example.com/a.js").is_err());
+        assert!(policy.check_url("https://esm.sh/evil-package").is_err());
+    }
+
+    #[test]
+    fn invalid_patterns_are_config_errors() {
+        let config = RemoteConfig {
+            deny: vec![UrlPattern::Regex { regex: "(".to_string() }],
+            ..RemoteConfig::default()
+        };
+        assert!(matches!(RemotePolicy::new(&config), Err(CompileError::BadConfig(_))));
+    }
+
+    #[test]
+    fn private_hosts_are_denied_unless_allowed() {
+        let denied = [
+            "http://127.0.0.1/",
+            "http://10.1.2.3/",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://[64:ff9b::a00:1]/",
+            "http://[2002:c0a8:101::]/",
+            "http://localhost/",
+            "http://app.localhost./",
+            "http://printer.local/",
+        ];
+        let strict = policy(RemoteConfig::default());
+        for url in denied {
+            assert!(strict.check_url(url).is_err(), "{} should be denied", url);
+        }
+        assert!(strict.check_url("http://93.184.215.14/").is_ok());
+        assert!(strict.check_url("http://[2606:4700::1111]/").is_ok());
+
+        let permissive = policy(RemoteConfig {
+            allow_private_networks: true,
+            ..RemoteConfig::default()
+        });
+        for url in denied {
+            assert!(permissive.check_url(url).is_ok(), "{} should be allowed", url);
+        }
+    }
+
+    #[test]
+    fn entries_are_only_checked_when_remote() {
+        let policy = policy(RemoteConfig::default());
+        assert!(policy.check_entry("./src/index.js").is_ok());
+        assert!(policy.check_entry("data:text/javascript,export default 1").is_ok());
+        assert!(matches!(policy.check_entry("http://127.0.0.1/a.js"), Err(CompileError::RemoteDenied(_))));
+    }
+
+    #[test]
+    fn non_public_ipv4_ranges() {
+        for address in [
+            "0.1.2.3",
+            "10.0.0.1",
+            "100.64.0.1",
+            "127.0.0.1",
+            "169.254.1.1",
+            "172.16.0.1",
+            "192.0.0.8",
+            "192.0.2.1",
+            "192.168.1.1",
+            "198.18.0.1",
+            "198.19.255.255",
+            "224.0.0.1",
+            "240.0.0.1",
+            "255.255.255.255",
+        ] {
+            assert!(!is_public(ip(address)), "{} should not be public", address);
+        }
+        for address in ["1.1.1.1", "8.8.8.8", "100.128.0.1", "198.20.0.1", "223.255.255.255"] {
+            assert!(is_public(ip(address)), "{} should be public", address);
+        }
+    }
+
+    #[test]
+    fn non_public_ipv6_ranges() {
+        for address in ["::", "::1", "fc00::1", "fd12::1", "fe80::1", "fec0::1", "ff02::1", "2001:db8::1"] {
+            assert!(!is_public(ip(address)), "{} should not be public", address);
+        }
+        assert!(is_public(ip("2606:4700::1111")));
+    }
+
+    #[test]
+    fn embedded_ipv4_addresses_are_checked() {
+        for address in [
+            // IPv4-mapped, IPv4-compatible, NAT64 and 6to4 forms of private addresses
+            "::ffff:10.0.0.1",
+            "::10.0.0.1",
+            "::127.0.0.1",
+            "64:ff9b::192.168.0.1",
+            "64:ff9b:1::a00:1",
+            "2002:7f00:1::",
+            "2002:a9fe:a9fe::1",
+        ] {
+            assert!(!is_public(ip(address)), "{} should not be public", address);
+        }
+        for address in ["::ffff:1.1.1.1", "64:ff9b::8.8.8.8", "2002:808:808::1"] {
+            assert!(is_public(ip(address)), "{} should be public", address);
+        }
+    }
+}
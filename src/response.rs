@@ -0,0 +1,205 @@
+use std::path::Path;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::compile_error::{CompileDiagnostic, CompileError};
+use crate::compiler_pool::RebuildInfo;
+use crate::edge_compile::{CompileOutput, VIRTUAL_ROOT};
+use crate::http_io::{FetchError, RedirectChain};
+use crate::integrity::sri;
+
+/// Structured body returned for `Accept: application/json` / `format=json` requests.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompileResponse {
+    pub id: String,
+    /// Whether the output was served from the compilation cache.
+    pub cached: bool,
+    pub duration_ms: f64,
+    /// How a warm compiler produced this output; absent for cache hits and one-shot builds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rebuild: Option<RebuildInfo>,
+    /// The `main` entry, or the first named one when the request has none.
+    pub entry: EntryMetadata,
+    pub entries: Vec<EntryMetadata>,
+    pub assets: Vec<AssetResponse>,
+    pub warnings: Vec<CompileDiagnostic>,
+    /// Redirects followed while fetching remote modules, absent when there were none.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<RedirectChain>,
+}
+
+/// Body returned when a compilation fails.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorResponse<'a> {
+    pub error: &'static str,
+    pub status: u16,
+    pub message: String,
+    pub diagnostics: &'a [CompileDiagnostic],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub fetch_errors: &'a [FetchError],
+}
+
+impl<'a> ErrorResponse<'a> {
+    pub fn new(error: &'a CompileError) -> Self {
+        Self {
+            error: error.kind(),
+            status: error.status_code().as_u16(),
+            message: error.to_string(),
+            diagnostics: error.diagnostics(),
+            fetch_errors: error.fetch_failures(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryMetadata {
+    pub name: String,
+    pub request: String,
+    pub kind: EntryKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    Local,
+    Remote,
+    Data,
+    /// Sources submitted in a `POST /compile` body.
+    Virtual,
+}
+
+impl EntryKind {
+    pub fn of(request: &str) -> Self {
+        if request.starts_with("http://") || request.starts_with("https://") {
+            EntryKind::Remote
+        } else if request.starts_with("data:") {
+            EntryKind::Data
+        } else if request.starts_with(VIRTUAL_ROOT) {
+            EntryKind::Virtual
+        } else {
+            EntryKind::Local
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetEncoding {
+    Utf8,
+    Base64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetResponse {
+    pub path: String,
+    /// Where the asset is served from, see `GET /bundle/<id>/<path>`.
+    pub url: String,
+    pub size: usize,
+    pub content_type: &'static str,
+    pub hash: String,
+    /// SRI hash for the `integrity` attribute of a `<script>` or `<link>` loading the asset.
+    pub integrity: String,
+    pub encoding: AssetEncoding,
+    pub content: String,
+}
+
+impl AssetResponse {
+    pub fn new(bundle_id: &str, path: String, content: &[u8]) -> Self {
+        let content_type = content_type_for(&path);
+        let hash = content_hash(content);
+        // Text assets are inlined as-is, anything that is not valid UTF-8 goes through base64.
+        let (encoding, content_str) = match std::str::from_utf8(content) {
+            Ok(text) => (AssetEncoding::Utf8, text.to_string()),
+            Err(_) => (AssetEncoding::Base64, BASE64.encode(content)),
+        };
+        Self {
+            size: content.len(),
+            url: bundle_url(bundle_id, &path),
+            path,
+            content_type,
+            hash,
+            integrity: sri(content),
+            encoding,
+            content: content_str,
+        }
+    }
+}
+
+impl CompileResponse {
+    pub fn new(duration: Duration, output: &CompileOutput, cached: bool, rebuild: Option<RebuildInfo>) -> Self {
+        let entries: Vec<EntryMetadata> = output
+            .entries
+            .iter()
+            .map(|(name, request)| EntryMetadata {
+                name: name.clone(),
+                kind: EntryKind::of(request),
+                request: request.clone(),
+            })
+            .collect();
+        let entry = EntryMetadata {
+            name: output
+                .entries
+                .iter()
+                .find(|(_, request)| **request == output.entry)
+                .map_or_else(|| "main".to_string(), |(name, _)| name.clone()),
+            kind: EntryKind::of(&output.entry),
+            request: output.entry.clone(),
+        };
+        let mut assets: Vec<AssetResponse> = output
+            .files
+            .iter()
+            .map(|(path, content)| AssetResponse::new(&output.id, path.clone(), content))
+            .collect();
+        assets.sort_by(|a, b| a.path.cmp(&b.path));
+        Self {
+            id: output.id.clone(),
+            cached,
+            duration_ms: duration.as_secs_f64() * 1000.0,
+            rebuild,
+            entry,
+            entries,
+            assets,
+            warnings: output.warnings.clone(),
+            redirects: output.redirects.clone(),
+        }
+    }
+}
+
+/// Hex SHA-256 of an asset's content.
+pub fn content_hash(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+pub fn bundle_url(bundle_id: &str, path: &str) -> String {
+    format!("/bundle/{}/{}", bundle_id, path)
+}
+
+/// Maps an emitted asset path to the `Content-Type` it should be served with.
+pub fn content_type_for(path: &str) -> &'static str {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    match extension {
+        "js" | "mjs" | "cjs" => "application/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "json" | "map" => "application/json; charset=utf-8",
+        "html" => "text/html; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "wasm" => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
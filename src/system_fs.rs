@@ -12,12 +12,11 @@ use rspack_paths::Utf8Path;
 use std::path::{Path, PathBuf};
 use std::fs::Metadata;
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct RealFileSystem;
 
 impl RealFileSystem {
     pub fn new() -> Self {
-        dbg!("Creating new RealFileSystem");
         Self
     }
 }
@@ -25,21 +24,18 @@ impl RealFileSystem {
 impl WritableFileSystem for RealFileSystem {
     fn create_dir(&self, dir: &Utf8Path) -> Result<()> {
         let dir_ref = dir.to_path_buf();
-        dbg!(&dir_ref);
         fs::create_dir(&dir_ref)?;
         Ok(())
     }
 
     fn create_dir_all(&self, dir: &Utf8Path) -> Result<()> {
         let dir_ref = dir.to_path_buf();
-        dbg!(&dir_ref);
         fs::create_dir_all(&dir_ref)?;
         Ok(())
     }
 
     fn write(&self, file: &Utf8Path, data: &[u8]) -> Result<()> {
         let file_ref = file.to_path_buf();
-        dbg!(&file_ref);
         fs::write(&file_ref, data)?;
         Ok(())
     }
@@ -67,7 +63,6 @@ impl ReadableFileSystem for RealFileSystem {
 impl AsyncWritableFileSystem for RealFileSystem {
     fn create_dir(&self, dir: &Utf8Path) -> BoxFuture<'_, Result<()>> {
         let dir_ref = dir.to_path_buf();
-        dbg!(&dir_ref);
         Box::pin(async move {
             tokio_fs::create_dir(&dir_ref).await?;
             Ok(())
@@ -76,7 +71,6 @@ impl AsyncWritableFileSystem for RealFileSystem {
 
     fn create_dir_all(&self, dir: &Utf8Path) -> BoxFuture<'_, Result<()>> {
         let dir_ref = dir.to_path_buf();
-        dbg!(&dir_ref);
         Box::pin(async move {
             tokio_fs::create_dir_all(&dir_ref).await?;
             Ok(())
@@ -86,7 +80,6 @@ impl AsyncWritableFileSystem for RealFileSystem {
     fn write(&self, file: &Utf8Path, data: &[u8]) -> BoxFuture<'_, Result<()>> {
         let file_ref = file.to_path_buf();
         let data = data.to_vec();
-        dbg!(&file_ref);
         Box::pin(async move {
             tokio_fs::write(&file_ref, &data).await?;
             Ok(())
@@ -95,7 +88,6 @@ impl AsyncWritableFileSystem for RealFileSystem {
 
     fn remove_file(&self, file: &Utf8Path) -> BoxFuture<'_, Result<()>> {
         let file_ref = file.to_path_buf();
-        dbg!(&file_ref);
         Box::pin(async move {
             tokio_fs::remove_file(&file_ref).await?;
             Ok(())
@@ -104,7 +96,6 @@ impl AsyncWritableFileSystem for RealFileSystem {
 
     fn remove_dir_all(&self, dir: &Utf8Path) -> BoxFuture<'_, Result<()>> {
         let dir_ref = dir.to_path_buf();
-        dbg!(&dir_ref);
         Box::pin(async move {
             tokio_fs::remove_dir_all(&dir_ref).await?;
             Ok(())
@@ -115,7 +106,6 @@ impl AsyncWritableFileSystem for RealFileSystem {
 impl AsyncReadableFileSystem for RealFileSystem {
     fn read(&self, file: &Utf8Path) -> BoxFuture<'_, rspack_fs::Result<Vec<u8>>> {
         let file_ref = file.to_path_buf();
-        dbg!(&file_ref);
         Box::pin(async move {
             let data = tokio_fs::read(&file_ref).await?;
             Ok(data)
@@ -0,0 +1,235 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use tokio::sync::mpsc;
+
+use crate::compile_cache::CacheKey;
+use crate::config::EdgeCompileConfig;
+use crate::edge_compile::CompileOutput;
+
+/// A watched compilation: what to rebuild when one of its dependencies changes.
+#[derive(Debug, Clone)]
+pub struct WatchTarget {
+    pub key: CacheKey,
+    pub entry: String,
+    pub config: EdgeCompileConfig,
+}
+
+#[derive(Default)]
+struct Watched {
+    targets: HashMap<CacheKey, WatchTarget>,
+    /// File dependencies and the targets depending on them.
+    files: HashMap<PathBuf, HashSet<CacheKey>>,
+    /// Context dependencies (directories) and the targets depending on them.
+    contexts: HashMap<PathBuf, HashSet<CacheKey>>,
+    /// Directories registered with the OS watcher.
+    directories: HashMap<PathBuf, RecursiveMode>,
+}
+
+impl Watched {
+    /// Replaces the dependencies of `target` with those of `output`. Returns the directories
+    /// to stop watching and those to start watching.
+    fn update(&mut self, target: WatchTarget, output: &CompileOutput) -> (Vec<PathBuf>, Vec<(PathBuf, RecursiveMode)>) {
+        let key = target.key.clone();
+        // Dependencies may have changed since the last build, so start from a clean slate
+        self.forget_dependencies(&key);
+        self.targets.insert(key.clone(), target);
+        for file in &output.file_dependencies {
+            self.files.entry(file.clone()).or_default().insert(key.clone());
+        }
+        for context in &output.context_dependencies {
+            self.contexts.entry(context.clone()).or_default().insert(key.clone());
+        }
+        self.sync_directories()
+    }
+
+    /// Drops the target `key` and its dependencies, like [`Watched::update`] returning the
+    /// directories to stop watching.
+    fn remove(&mut self, key: &CacheKey) -> (Vec<PathBuf>, Vec<(PathBuf, RecursiveMode)>) {
+        self.targets.remove(key);
+        self.forget_dependencies(key);
+        self.sync_directories()
+    }
+
+    fn forget_dependencies(&mut self, key: &CacheKey) {
+        for keys in self.files.values_mut().chain(self.contexts.values_mut()) {
+            keys.remove(key);
+        }
+        self.files.retain(|_, keys| !keys.is_empty());
+        self.contexts.retain(|_, keys| !keys.is_empty());
+    }
+
+    // Compares the directories the dependencies need with those registered so far.
+    fn sync_directories(&mut self) -> (Vec<PathBuf>, Vec<(PathBuf, RecursiveMode)>) {
+        let mut wanted: HashMap<PathBuf, RecursiveMode> = self
+            .files
+            .keys()
+            .filter_map(|file| file.parent())
+            .map(|parent| (parent.to_path_buf(), RecursiveMode::NonRecursive))
+            .collect();
+        for context in self.contexts.keys() {
+            wanted.insert(context.clone(), RecursiveMode::Recursive);
+        }
+        let unwatch: Vec<PathBuf> = self
+            .directories
+            .iter()
+            .filter(|(directory, mode)| wanted.get(*directory) != Some(*mode))
+            .map(|(directory, _)| directory.clone())
+            .collect();
+        let watch: Vec<(PathBuf, RecursiveMode)> = wanted
+            .iter()
+            .filter(|(directory, mode)| self.directories.get(*directory) != Some(*mode))
+            .map(|(directory, mode)| (directory.clone(), *mode))
+            .collect();
+        self.directories = wanted;
+        (unwatch, watch)
+    }
+
+    fn affected_by(&self, path: &Path) -> HashSet<CacheKey> {
+        let mut keys = self.files.get(path).cloned().unwrap_or_default();
+        for (context, context_keys) in &self.contexts {
+            if path.starts_with(context) {
+                keys.extend(context_keys.iter().cloned());
+            }
+        }
+        keys
+    }
+}
+
+/// Watches the file and context dependencies of local entries and reports which watched
+/// compilations are dirty.
+///
+/// Parent directories are watched rather than the files themselves, so editors that save by
+/// replacing the file are picked up too.
+pub struct Watcher {
+    watcher: Mutex<RecommendedWatcher>,
+    watched: Arc<Mutex<Watched>>,
+}
+
+impl Watcher {
+    /// Returns the watcher and the receiving end of its dirty notifications.
+    pub fn new() -> notify::Result<(Self, mpsc::UnboundedReceiver<WatchTarget>)> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let watched = Arc::new(Mutex::new(Watched::default()));
+        let callback_watched = watched.clone();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else { return };
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+            let watched = callback_watched.lock().unwrap();
+            let keys: HashSet<CacheKey> = event
+                .paths
+                .iter()
+                .flat_map(|path| watched.affected_by(path))
+                .collect();
+            for key in keys {
+                if let Some(target) = watched.targets.get(&key) {
+                    let _ = sender.send(target.clone());
+                }
+            }
+        })?;
+        Ok((
+            Self {
+                watcher: Mutex::new(watcher),
+                watched,
+            },
+            receiver,
+        ))
+    }
+
+    /// Starts (or refreshes) watching the dependencies of `output`, and stops watching
+    /// directories no target depends on anymore.
+    pub fn watch(&self, target: WatchTarget, output: &CompileOutput) -> notify::Result<()> {
+        // Held throughout so concurrent calls apply their changes to the OS watcher in order.
+        // `watched` must not be held while calling into it: the event callback locks
+        // `watched`, and the watcher may wait for its event thread to pick up the change.
+        let mut watcher = self.watcher.lock().unwrap();
+        let changes = self.watched.lock().unwrap().update(target, output);
+        self.apply(&mut watcher, changes)
+    }
+
+    /// Stops watching the target `key`, e.g. once its compiler is gone, along with the
+    /// directories only it depended on.
+    pub fn unwatch(&self, key: &CacheKey) -> notify::Result<()> {
+        let mut watcher = self.watcher.lock().unwrap();
+        let changes = self.watched.lock().unwrap().remove(key);
+        self.apply(&mut watcher, changes)
+    }
+
+    fn apply(
+        &self,
+        watcher: &mut RecommendedWatcher,
+        (unwatch, watch): (Vec<PathBuf>, Vec<(PathBuf, RecursiveMode)>),
+    ) -> notify::Result<()> {
+        for directory in unwatch {
+            // The directory may be gone already, which removed its watch too
+            let _ = watcher.unwatch(&directory);
+        }
+        let mut result = Ok(());
+        for (directory, mode) in watch {
+            if let Err(err) = watcher.watch(&directory, mode) {
+                // Not registered, so a later call tries again
+                self.watched.lock().unwrap().directories.remove(&directory);
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edge_compile::CompileInput;
+
+    fn target(entry: &str) -> WatchTarget {
+        let config = EdgeCompileConfig::default();
+        WatchTarget {
+            key: CacheKey::new(&CompileInput::Entry(Some(entry.to_string())), &config),
+            entry: entry.to_string(),
+            config,
+        }
+    }
+
+    fn output(file_dependencies: &[&str]) -> CompileOutput {
+        CompileOutput {
+            id: String::new(),
+            compiler_id: String::new(),
+            hash: None,
+            entry: String::new(),
+            entries: Default::default(),
+            files: HashMap::new(),
+            warnings: Vec::new(),
+            file_dependencies: file_dependencies.iter().map(PathBuf::from).collect(),
+            context_dependencies: Vec::new(),
+            remote_modules: Vec::new(),
+            redirects: Vec::new(),
+        }
+    }
+
+    fn directories(changes: Vec<(PathBuf, RecursiveMode)>) -> Vec<PathBuf> {
+        let mut directories: Vec<PathBuf> = changes.into_iter().map(|(directory, _)| directory).collect();
+        directories.sort();
+        directories
+    }
+
+    #[test]
+    fn removed_targets_release_the_directories_only_they_need() {
+        let mut watched = Watched::default();
+        let (a, b) = (target("./a.js"), target("./b.js"));
+        let (_, watch) = watched.update(a.clone(), &output(&["/src/a.js", "/shared/util.js"]));
+        assert_eq!(directories(watch), [PathBuf::from("/shared"), PathBuf::from("/src")]);
+        let (_, watch) = watched.update(b.clone(), &output(&["/lib/b.js", "/shared/util.js"]));
+        assert_eq!(directories(watch), [PathBuf::from("/lib")]);
+
+        let (unwatch, watch) = watched.remove(&a.key);
+        assert_eq!(unwatch, [PathBuf::from("/src")]);
+        assert!(watch.is_empty());
+        assert!(!watched.targets.contains_key(&a.key));
+        assert!(watched.affected_by(Path::new("/src/a.js")).is_empty());
+        assert_eq!(watched.affected_by(Path::new("/shared/util.js")), HashSet::from([b.key]));
+    }
+}